@@ -1,23 +1,33 @@
+use nn_rust::ec::config::EcConfig;
 use nn_rust::ec::evolutionary_computation::EvolutionaryComputation;
 use nn_rust::nn::data_set::DataSet;
 
 fn main() {
-    let seed = 123456;
-    let generations = 100;
-    let population_size = 100;
-    let max_epochs = 1000;
-    let max_epochs_start = 500;
-    let max_calculations = 10000;
-    let max_calculations_start = 10000;
-    let max_layer_count = 5;
-    let max_nodes = 1000;
-    let max_nodes_start = 100;
-    let max_learning_rate = 0.0001;
-    let min_epochs = 20;
-    let train_set: DataSet = DataSet::get_from_file("data.txt");
-    let test_set: DataSet = DataSet::get_from_file("data.txt");
-    let mut ec = EvolutionaryComputation::new(train_set, seed, generations, population_size, 4, 7, max_epochs, max_epochs_start, max_calculations, max_calculations_start, max_layer_count, max_nodes, max_nodes_start, max_learning_rate, min_epochs, test_set);
+    // an optional config file (.toml or .json) replaces the settings below
+    let config = match std::env::args().nth(1) {
+        Some(file) => EcConfig::from_file(&file),
+        None => EcConfig::builder()
+            .seed(123456)
+            .generations(100)
+            .population_size(100)
+            .min_epochs(20)
+            .max_epochs_start(500)
+            .max_epochs(1000)
+            .max_calculations_start(10000)
+            .max_calculations(10000)
+            .max_layer_count(5)
+            .max_nodes_start(100)
+            .max_nodes(1000)
+            .max_learning_rate(0.1)
+            .build(),
+    }.unwrap();
+    let train_set: DataSet = DataSet::get_from_file("src/nn/data.txt").unwrap();
+    let test_set: DataSet = DataSet::get_from_file("src/nn/data.txt").unwrap();
+    let mut ec = EvolutionaryComputation::new(config, train_set, test_set).unwrap();
     println!("init done");
-    ec.run();
+    let result = ec.run();
     println!("finished");
+    if let Some(best) = result.hall_of_fame.best() {
+        println!("Best of all generations: \n {}", best);
+    }
 }
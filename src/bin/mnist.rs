@@ -0,0 +1,32 @@
+use std::env;
+use std::time::Instant;
+use nn_rust::nn::activation::Activation;
+use nn_rust::nn::data_set::DataSet;
+use nn_rust::nn::loss::SoftmaxCrossEntropy;
+use nn_rust::nn::neural_network::NeuralNetwork;
+use nn_rust::nn::optimizer::OptimizerConfig;
+
+// usage: mnist [directory with the (optionally gzipped) MNIST IDX files]
+fn main() {
+    let dir = env::args().nth(1).unwrap_or_else(|| "data/mnist".to_string());
+    let file = |name: &str| {
+        let gz = format!("{}/{}.gz", dir, name);
+        if std::path::Path::new(&gz).exists() { gz } else { format!("{}/{}", dir, name) }
+    };
+    let train_set = DataSet::from_idx(&file("train-images-idx3-ubyte"), &file("train-labels-idx1-ubyte"), 10).unwrap();
+    let test_set = DataSet::from_idx(&file("t10k-images-idx3-ubyte"), &file("t10k-labels-idx1-ubyte"), 10).unwrap();
+    println!("loaded {} training and {} test images", train_set.inputs.len(), test_set.inputs.len());
+
+    let mut nn = NeuralNetwork::new_with_activations(vec![784, 128, 10], vec![Activation::Relu, Activation::Softmax], 0.001, 1);
+    nn.loss = Box::new(SoftmaxCrossEntropy);
+    nn.set_optimizer(OptimizerConfig::Adam { beta1: 0.9, beta2: 0.999, epsilon: 1e-8 });
+    nn.batch_size = 32;
+    for epoch in 0..5 {
+        let start = Instant::now();
+        let loss = nn.fit(&train_set.inputs, &train_set.targets, 1)[0];
+        let correct = test_set.inputs.iter().zip(&test_set.targets)
+            .filter(|(input, target)| test_set.decode(&nn.predict(input.to_vec())) == test_set.decode(target))
+            .count();
+        println!("epoch {}: loss {:.4}, test accuracy {:.2}%, {:?}", epoch, loss, correct as f64 / test_set.inputs.len() as f64 * 100.0, start.elapsed());
+    }
+}
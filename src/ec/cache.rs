@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::ec::contestant::Contestant;
+use crate::ec::evaluation_context::EvaluationContext;
+use crate::ec::genome::Genome;
+use crate::nn::data_set::DataSet;
+use crate::nn::metrics::Metrics;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// 64 bit FNV-1a, unlike the std hasher it's the same on every platform and in every run
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+pub fn data_fingerprint(data_sets: &[&DataSet]) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+    for data_set in data_sets {
+        hash = fnv1a(hash, &(data_set.inputs.len() as u64).to_le_bytes());
+        for row in data_set.inputs.iter().chain(&data_set.targets) {
+            hash = fnv1a(hash, &(row.len() as u64).to_le_bytes());
+            for value in row {
+                hash = fnv1a(hash, &value.to_bits().to_le_bytes());
+            }
+        }
+    }
+    hash
+}
+
+// everything that decides the outcome of Contestant::fit, the network and the shuffle start from the seed
+#[derive(Serialize)]
+struct GenomeKey<'a> {
+    layer_sizes: &'a [i32],
+    epochs: i32,
+    learning_rate: f64,
+    seed: i32,
+    genome: &'a Genome,
+}
+
+// hash of the genome of the contestant and the data sets it gets trained and tested on
+pub fn genome_hash(contestant: &Contestant, context: &EvaluationContext) -> u64 {
+    let key = GenomeKey {
+        layer_sizes: &contestant.layer_sizes,
+        epochs: contestant.epochs,
+        learning_rate: contestant.learning_rate,
+        seed: contestant.seed,
+        genome: &contestant.genome,
+    };
+    let json = serde_json::to_vec(&key).expect("genome_hash: could not serialize the genome");
+    fnv1a(context.fingerprint, &json)
+}
+
+// the results of Contestant::fit, the cost is left to the fitness function
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedEvaluation {
+    pub accuracy: f64,
+    pub average_error: f64,
+    pub max_error: f64,
+    pub max_error_index: usize,
+    pub metrics: Metrics,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EvaluationCache {
+    entries: HashMap<u64, CachedEvaluation>,
+}
+
+impl EvaluationCache {
+    pub fn new() -> EvaluationCache {
+        EvaluationCache::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: u64) -> Option<&CachedEvaluation> {
+        self.entries.get(&key)
+    }
+
+    // contestants that weren't evaluated (or were restored from the cache themselves) are skipped
+    pub fn insert(&mut self, key: u64, contestant: &Contestant) {
+        if let (Some(metrics), false) = (&contestant.metrics, contestant.cached) {
+            self.entries.entry(key).or_insert_with(|| CachedEvaluation {
+                accuracy: contestant.accuracy,
+                average_error: contestant.average_error,
+                max_error: contestant.max_error,
+                max_error_index: contestant.max_error_index,
+                metrics: metrics.clone(),
+            });
+        }
+    }
+
+    // fills in the results of fit if the key is known, the network of the contestant stays untrained
+    pub fn restore(&self, key: u64, contestant: &mut Contestant) -> bool {
+        match self.entries.get(&key) {
+            Some(evaluation) => {
+                contestant.accuracy = evaluation.accuracy;
+                contestant.average_error = evaluation.average_error;
+                contestant.max_error = evaluation.max_error;
+                contestant.max_error_index = evaluation.max_error_index;
+                contestant.metrics = Some(evaluation.metrics.clone());
+                contestant.evaluated = true;
+                contestant.cached = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // writes to a temporary file first, like checkpoints
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, serde_json::to_vec(self)?)?;
+        fs::rename(&temporary, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<EvaluationCache> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::activation::Activation;
+    use crate::nn::optimizer::OptimizerConfig;
+
+    fn context(first_class: f64) -> EvaluationContext {
+        let data_set = DataSet::new(vec![vec![0.0, 1.0], vec![1.0, 0.0]], vec![vec![first_class, 1.0 - first_class], vec![0.0, 1.0]]);
+        EvaluationContext::new(data_set.clone(), data_set, 2, 2)
+    }
+
+    fn contestant(epochs: i32, context: &EvaluationContext) -> Contestant {
+        let genome = Genome { activations: vec![Activation::Sigmoid; 2], output_activation: Activation::Sigmoid, optimizer: OptimizerConfig::default(), batch_size: 1, l2: 0.0 };
+        Contestant::new(epochs, 3, vec![7, 0], 0.1, genome, context)
+    }
+
+    #[test]
+    fn fnv1a_reference_values() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), FNV_OFFSET_BASIS);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn genome_hash_depends_on_the_genome_and_the_data() {
+        let context = context(1.0);
+        let mut contestant = contestant(2, &context);
+        let key = genome_hash(&contestant, &context);
+        // training changes the network and the results, not the genome
+        contestant.fit(&context);
+        assert_eq!(genome_hash(&contestant, &context), key);
+        assert_eq!(genome_hash(&contestant, &self::context(1.0)), key);
+        contestant.epochs = 3;
+        assert_ne!(genome_hash(&contestant, &context), key);
+        contestant.epochs = 2;
+        contestant.genome.batch_size = 2;
+        assert_ne!(genome_hash(&contestant, &context), key);
+        contestant.genome.batch_size = 1;
+        let other_data = self::context(0.0);
+        assert_ne!(other_data.fingerprint, context.fingerprint);
+        assert_ne!(genome_hash(&contestant, &other_data), key);
+    }
+
+    #[test]
+    fn hits_restore_the_results_of_fit() {
+        let context = context(1.0);
+        let mut evaluated = contestant(2, &context);
+        evaluated.fit(&context);
+        let key = genome_hash(&evaluated, &context);
+        let mut cache = EvaluationCache::new();
+        cache.insert(key, &evaluated);
+        assert_eq!(cache.len(), 1);
+
+        let mut restored = contestant(2, &context);
+        assert!(!cache.restore(key.wrapping_add(1), &mut restored));
+        assert!(!restored.evaluated && restored.metrics.is_none());
+        assert!(cache.restore(key, &mut restored));
+        assert!(restored.evaluated && restored.cached);
+        assert_eq!((restored.accuracy, restored.average_error, restored.max_error, restored.max_error_index),
+                   (evaluated.accuracy, evaluated.average_error, evaluated.max_error, evaluated.max_error_index));
+        assert_eq!(restored.metrics, evaluated.metrics);
+        // the network isn't trained
+        let untrained = contestant(2, &context);
+        assert_eq!(restored.neural_network.weights[0].matrix, untrained.neural_network.weights[0].matrix);
+    }
+
+    #[test]
+    fn only_fitted_contestants_are_inserted() {
+        let context = context(1.0);
+        let mut cache = EvaluationCache::new();
+        let mut contestant = contestant(2, &context);
+        cache.insert(1, &contestant);
+        assert!(cache.is_empty());
+        contestant.fit(&context);
+        cache.insert(1, &contestant);
+        let mut restored = self::contestant(5, &context);
+        assert!(cache.restore(1, &mut restored));
+        cache.insert(2, &restored);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(2).is_none());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = std::env::temp_dir().join(format!("nn_rust_cache_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let context = context(1.0);
+        let mut contestant = contestant(2, &context);
+        contestant.fit(&context);
+        let mut cache = EvaluationCache::new();
+        cache.insert(7, &contestant);
+        cache.save(dir.join("cache.json")).unwrap();
+        assert!(!dir.join("cache.json.tmp").exists());
+        let loaded = EvaluationCache::load(dir.join("cache.json")).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.get(7).unwrap().metrics, contestant.metrics.unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::ec::config::EcConfig;
+use crate::ec::contestant::Contestant;
+use crate::ec::hall_of_fame::HallOfFame;
+
+// like MODEL_FORMAT_VERSION it only changes when old files can't be read anymore,
+// fields that get added later need #[serde(default)]
+pub const CHECKPOINT_FORMAT_VERSION: u32 = 1;
+// checkpoint files start with these bytes followed by the format version (u32, little endian)
+const CHECKPOINT_MAGIC: &[u8; 4] = b"ECCP";
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(std::io::Error),
+    Encode(rmp_serde::encode::Error),
+    Decode(rmp_serde::decode::Error),
+    UnsupportedVersion(u32),
+    Invalid(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "io error: {}", e),
+            CheckpointError::Encode(e) => write!(f, "could not encode checkpoint: {}", e),
+            CheckpointError::Decode(e) => write!(f, "invalid checkpoint: {}", e),
+            CheckpointError::UnsupportedVersion(v) => write!(f, "unsupported checkpoint format version {} (supported: {})", v, CHECKPOINT_FORMAT_VERSION),
+            CheckpointError::Invalid(e) => write!(f, "invalid checkpoint: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<std::io::Error> for CheckpointError {
+    fn from(e: std::io::Error) -> CheckpointError {
+        CheckpointError::Io(e)
+    }
+}
+
+impl From<rmp_serde::encode::Error> for CheckpointError {
+    fn from(e: rmp_serde::encode::Error) -> CheckpointError {
+        CheckpointError::Encode(e)
+    }
+}
+
+impl From<rmp_serde::decode::Error> for CheckpointError {
+    fn from(e: rmp_serde::decode::Error) -> CheckpointError {
+        CheckpointError::Decode(e)
+    }
+}
+
+// everything needed to continue a run, the random streams are derived from the seed
+// in the config and the generation, so they need no state of their own
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub config: EcConfig,
+    // the generation that gets evaluated next
+    pub generation: i32,
+    pub population: Vec<Contestant>,
+    pub hall_of_fame: HallOfFame,
+    // the name of the fitness function, it can't be stored itself
+    pub fitness_function: String,
+    // of the data sets the run was started with, see EvaluationContext
+    pub fingerprint: u64,
+}
+
+impl Checkpoint {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CheckpointError> {
+        let mut bytes = CHECKPOINT_MAGIC.to_vec();
+        bytes.extend_from_slice(&CHECKPOINT_FORMAT_VERSION.to_le_bytes());
+        bytes.extend(rmp_serde::to_vec(self)?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Checkpoint, CheckpointError> {
+        if bytes.len() < 8 || &bytes[0..4] != CHECKPOINT_MAGIC {
+            return Err(CheckpointError::Invalid("not a checkpoint file".to_string()));
+        }
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != CHECKPOINT_FORMAT_VERSION {
+            return Err(CheckpointError::UnsupportedVersion(version));
+        }
+        Ok(rmp_serde::from_slice(&bytes[8..])?)
+    }
+
+    // writes to a temporary file first, so an interrupted save doesn't destroy the last checkpoint
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CheckpointError> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, self.to_bytes()?)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Checkpoint, CheckpointError> {
+        Checkpoint::from_bytes(&fs::read(path)?)
+    }
+}
@@ -0,0 +1,562 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use crate::ec::fitness::{ErrorCost, FitnessFunction};
+use crate::ec::selection::SelectionConfig;
+use crate::ec::speciation::SpeciationConfig;
+use crate::nn::activation::Activation;
+use crate::nn::optimizer::OptimizerConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SearchMode {
+    // one fitness from the cost, uses `selection` and `elitism`
+    #[default]
+    SingleObjective,
+    // NSGA-II over (cost, calculations, epochs), parents and offspring compete for survival
+    Nsga2,
+}
+
+// settings of an EvolutionaryComputation run, missing fields in config files use the defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EcConfig {
+    pub seed: i32,
+    pub generations: i32,
+    pub population_size: i32,
+    // epochs of the first generation are in min_epochs..max_epochs_start
+    pub min_epochs: i32,
+    pub max_epochs_start: i32,
+    pub max_epochs: i32,
+    // calculations of a contestant are the number of weights of its network
+    pub max_calculations_start: i32,
+    pub max_calculations: i32,
+    // hidden layer slots, empty slots are skipped
+    pub max_layer_count: i32,
+    pub max_nodes_start: i32,
+    pub max_nodes: i32,
+    // learning rates are drawn from 0..max_learning_rate and mutated within it
+    pub max_learning_rate: f64,
+    // choices for the activations of the hidden layers and the output layer
+    pub hidden_activations: Vec<Activation>,
+    pub output_activations: Vec<Activation>,
+    // choices for the optimizer, their hyperparameters are where new contestants start
+    pub optimizers: Vec<OptimizerConfig>,
+    pub max_batch_size: usize,
+    // 0 disables the L2 penalty
+    pub max_l2: f64,
+    // chance that an activation or the optimizer gets replaced by a random choice when mutating
+    pub gene_mutation_rate: f64,
+    // chances that a child is a mutated copy of one parent or a mutated mix of two,
+    // the remaining children are new random contestants
+    pub asexual_reproduction: f64,
+    pub sexual_reproduction: f64,
+    // chance per layer slot when mutating that a used slot changes its size instead of getting
+    // removed and that an empty one gets a new layer
+    pub asexual_keep_rate: f64,
+    // chance that a mixed value is taken from one parent instead of being their average
+    pub sexual_keep_rate: f64,
+    // children start from the trained weights of their parents instead of a fresh initialization
+    pub lamarckian: bool,
+    // worker threads used to train the contestants, 0 uses one per core
+    pub threads: usize,
+    // can't be part of a config file, ErrorCost is used for runs loaded from one,
+    // checkpoints only store its name so resume can check the one it gets
+    #[serde(skip, default = "default_fitness_function")]
+    pub fitness_function: Arc<dyn FitnessFunction>,
+    // the fitness is best_cost^cost_scaler / cost^cost_scaler
+    pub cost_scaler: f64,
+    pub search: SearchMode,
+    pub selection: SelectionConfig,
+    // number of best contestants copied unchanged into the next generation
+    pub elitism: usize,
+    // fitness sharing between similar contestants, none if missing
+    pub speciation: Option<SpeciationConfig>,
+    // a checkpoint is written to checkpoint_path every checkpoint_every generations, 0 disables them
+    pub checkpoint_every: i32,
+    pub checkpoint_path: Option<String>,
+    // statistics of every generation are written to this .csv or .jsonl file
+    pub stats_path: Option<String>,
+    // reuse the results of contestants whose genome was already evaluated
+    pub cache: bool,
+    // the cache is loaded from this file at the start of a run and written to it after every generation
+    pub cache_path: Option<String>,
+    // number of the best contestants of the whole run that are kept with their trained networks
+    pub hall_of_fame_size: usize,
+    // the hall of fame is saved into this directory at the end of a run
+    pub hall_of_fame_path: Option<String>,
+}
+
+fn default_fitness_function() -> Arc<dyn FitnessFunction> {
+    Arc::new(ErrorCost)
+}
+
+impl Default for EcConfig {
+    fn default() -> EcConfig {
+        EcConfig {
+            seed: 123456,
+            generations: 100,
+            population_size: 100,
+            min_epochs: 20,
+            max_epochs_start: 500,
+            max_epochs: 1000,
+            max_calculations_start: 10000,
+            max_calculations: 10000,
+            max_layer_count: 5,
+            max_nodes_start: 100,
+            max_nodes: 1000,
+            max_learning_rate: 0.1,
+            hidden_activations: vec![Activation::Sigmoid, Activation::Relu, Activation::LeakyRelu, Activation::Tanh, Activation::Softplus, Activation::Gelu],
+            output_activations: vec![Activation::Sigmoid, Activation::Softmax],
+            optimizers: vec![
+                OptimizerConfig::Sgd { momentum: 0.9, nesterov: false },
+                OptimizerConfig::RmsProp { decay: 0.9, epsilon: 1e-8 },
+                OptimizerConfig::Adam { beta1: 0.9, beta2: 0.999, epsilon: 1e-8 },
+                OptimizerConfig::AdamW { beta1: 0.9, beta2: 0.999, epsilon: 1e-8, weight_decay: 0.01 },
+            ],
+            max_batch_size: 32,
+            max_l2: 0.001,
+            gene_mutation_rate: 0.1,
+            asexual_reproduction: 0.75,
+            sexual_reproduction: 0.22,
+            asexual_keep_rate: 0.95,
+            sexual_keep_rate: 0.3,
+            lamarckian: false,
+            threads: 0,
+            fitness_function: default_fitness_function(),
+            cost_scaler: 1.2,
+            search: SearchMode::default(),
+            selection: SelectionConfig::default(),
+            elitism: 0,
+            speciation: None,
+            checkpoint_every: 0,
+            checkpoint_path: None,
+            stats_path: None,
+            cache: false,
+            cache_path: None,
+            hall_of_fame_size: 5,
+            hall_of_fame_path: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    UnknownFormat(String),
+    Invalid(String),
+    // the training and test set don't fit together
+    DataSets(String),
+    ThreadPool(rayon::ThreadPoolBuildError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "io error: {}", e),
+            ConfigError::Toml(e) => write!(f, "invalid toml config: {}", e),
+            ConfigError::Json(e) => write!(f, "invalid json config: {}", e),
+            ConfigError::UnknownFormat(file) => write!(f, "can't tell the format of '{}', expected a .toml or .json file", file),
+            ConfigError::Invalid(e) => write!(f, "invalid config: {}", e),
+            ConfigError::DataSets(e) => write!(f, "invalid data sets: {}", e),
+            ConfigError::ThreadPool(e) => write!(f, "could not create the thread pool: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> ConfigError {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> ConfigError {
+        ConfigError::Toml(e)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(e: serde_json::Error) -> ConfigError {
+        ConfigError::Json(e)
+    }
+}
+
+impl From<rayon::ThreadPoolBuildError> for ConfigError {
+    fn from(e: rayon::ThreadPoolBuildError) -> ConfigError {
+        ConfigError::ThreadPool(e)
+    }
+}
+
+impl EcConfig {
+    pub fn builder() -> EcConfigBuilder {
+        EcConfigBuilder { config: EcConfig::default() }
+    }
+
+    // the format is picked by the file extension (.toml or .json)
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<EcConfig, ConfigError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        let config: EcConfig = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&contents)?,
+            Some("json") => serde_json::from_str(&contents)?,
+            _ => return Err(ConfigError::UnknownFormat(path.display().to_string())),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: String| Err(ConfigError::Invalid(message));
+        if self.population_size <= 0 {
+            return invalid(format!("population_size must be positive, got {}", self.population_size));
+        }
+        if self.generations < 0 {
+            return invalid(format!("generations must not be negative, got {}", self.generations));
+        }
+        if self.min_epochs < 1 {
+            return invalid(format!("min_epochs must be at least 1, got {}", self.min_epochs));
+        }
+        if self.min_epochs >= self.max_epochs_start {
+            return invalid(format!("min_epochs ({}) must be smaller than max_epochs_start ({})", self.min_epochs, self.max_epochs_start));
+        }
+        if self.max_epochs_start > self.max_epochs {
+            return invalid(format!("max_epochs_start ({}) must not be larger than max_epochs ({})", self.max_epochs_start, self.max_epochs));
+        }
+        if self.max_calculations_start <= 0 || self.max_calculations_start > self.max_calculations {
+            return invalid(format!("max_calculations_start ({}) must be positive and not larger than max_calculations ({})", self.max_calculations_start, self.max_calculations));
+        }
+        if self.max_layer_count <= 0 {
+            return invalid(format!("max_layer_count must be positive, got {}", self.max_layer_count));
+        }
+        if self.max_nodes_start <= 0 || self.max_nodes_start > self.max_nodes {
+            return invalid(format!("max_nodes_start ({}) must be positive and not larger than max_nodes ({})", self.max_nodes_start, self.max_nodes));
+        }
+        if !(self.max_learning_rate > 0.0 && self.max_learning_rate.is_finite()) {
+            return invalid(format!("max_learning_rate must be positive, got {}", self.max_learning_rate));
+        }
+        if self.hidden_activations.is_empty() || self.output_activations.is_empty() || self.optimizers.is_empty() {
+            return invalid("hidden_activations, output_activations and optimizers need at least one choice".to_string());
+        }
+        if self.max_batch_size == 0 {
+            return invalid("max_batch_size must be at least 1".to_string());
+        }
+        if !(self.max_l2 >= 0.0 && self.max_l2.is_finite()) {
+            return invalid(format!("max_l2 must not be negative, got {}", self.max_l2));
+        }
+        if !(0.0..=1.0).contains(&self.gene_mutation_rate) {
+            return invalid(format!("gene_mutation_rate must be in 0..=1, got {}", self.gene_mutation_rate));
+        }
+        for (name, rate) in [("asexual_reproduction", self.asexual_reproduction), ("sexual_reproduction", self.sexual_reproduction),
+                             ("asexual_keep_rate", self.asexual_keep_rate), ("sexual_keep_rate", self.sexual_keep_rate)] {
+            if !(0.0..=1.0).contains(&rate) {
+                return invalid(format!("{} must be in 0..=1, got {}", name, rate));
+            }
+        }
+        if self.asexual_reproduction + self.sexual_reproduction > 1.0 {
+            return invalid(format!("asexual_reproduction and sexual_reproduction add up to more than 1 ({} + {})", self.asexual_reproduction, self.sexual_reproduction));
+        }
+        if !(self.cost_scaler > 0.0 && self.cost_scaler.is_finite()) {
+            return invalid(format!("cost_scaler must be positive, got {}", self.cost_scaler));
+        }
+        if let Err(e) = self.selection.validate() {
+            return invalid(e);
+        }
+        if let Some(speciation) = &self.speciation {
+            if let Err(e) = speciation.validate() {
+                return invalid(e);
+            }
+            if self.search != SearchMode::SingleObjective {
+                return invalid("speciation only works with the SingleObjective search".to_string());
+            }
+        }
+        if self.elitism >= self.population_size as usize {
+            return invalid(format!("elitism ({}) must be smaller than population_size ({})", self.elitism, self.population_size));
+        }
+        if self.checkpoint_every < 0 {
+            return invalid(format!("checkpoint_every must not be negative, got {}", self.checkpoint_every));
+        }
+        if self.checkpoint_every > 0 && self.checkpoint_path.is_none() {
+            return invalid("checkpoint_every is set but there is no checkpoint_path".to_string());
+        }
+        if let Some(path) = &self.stats_path {
+            if !path.ends_with(".csv") && !path.ends_with(".jsonl") {
+                return invalid(format!("stats_path has to be a .csv or .jsonl file, got '{}'", path));
+            }
+        }
+        if self.cache_path.is_some() && !self.cache {
+            return invalid("cache_path is set but the cache is disabled".to_string());
+        }
+        if self.hall_of_fame_path.is_some() && self.hall_of_fame_size == 0 {
+            return invalid("hall_of_fame_path is set but hall_of_fame_size is 0".to_string());
+        }
+        // the results of lamarckian children depend on the weights they inherit, not only on their genome
+        if self.cache && self.lamarckian {
+            return invalid("the cache can't be used with lamarckian".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub struct EcConfigBuilder {
+    config: EcConfig,
+}
+
+impl EcConfigBuilder {
+    pub fn seed(mut self, seed: i32) -> EcConfigBuilder {
+        self.config.seed = seed;
+        self
+    }
+
+    pub fn generations(mut self, generations: i32) -> EcConfigBuilder {
+        self.config.generations = generations;
+        self
+    }
+
+    pub fn population_size(mut self, population_size: i32) -> EcConfigBuilder {
+        self.config.population_size = population_size;
+        self
+    }
+
+    pub fn min_epochs(mut self, min_epochs: i32) -> EcConfigBuilder {
+        self.config.min_epochs = min_epochs;
+        self
+    }
+
+    pub fn max_epochs_start(mut self, max_epochs_start: i32) -> EcConfigBuilder {
+        self.config.max_epochs_start = max_epochs_start;
+        self
+    }
+
+    pub fn max_epochs(mut self, max_epochs: i32) -> EcConfigBuilder {
+        self.config.max_epochs = max_epochs;
+        self
+    }
+
+    pub fn max_calculations_start(mut self, max_calculations_start: i32) -> EcConfigBuilder {
+        self.config.max_calculations_start = max_calculations_start;
+        self
+    }
+
+    pub fn max_calculations(mut self, max_calculations: i32) -> EcConfigBuilder {
+        self.config.max_calculations = max_calculations;
+        self
+    }
+
+    pub fn max_layer_count(mut self, max_layer_count: i32) -> EcConfigBuilder {
+        self.config.max_layer_count = max_layer_count;
+        self
+    }
+
+    pub fn max_nodes_start(mut self, max_nodes_start: i32) -> EcConfigBuilder {
+        self.config.max_nodes_start = max_nodes_start;
+        self
+    }
+
+    pub fn max_nodes(mut self, max_nodes: i32) -> EcConfigBuilder {
+        self.config.max_nodes = max_nodes;
+        self
+    }
+
+    pub fn max_learning_rate(mut self, max_learning_rate: f64) -> EcConfigBuilder {
+        self.config.max_learning_rate = max_learning_rate;
+        self
+    }
+
+    pub fn hidden_activations(mut self, hidden_activations: Vec<Activation>) -> EcConfigBuilder {
+        self.config.hidden_activations = hidden_activations;
+        self
+    }
+
+    pub fn output_activations(mut self, output_activations: Vec<Activation>) -> EcConfigBuilder {
+        self.config.output_activations = output_activations;
+        self
+    }
+
+    pub fn optimizers(mut self, optimizers: Vec<OptimizerConfig>) -> EcConfigBuilder {
+        self.config.optimizers = optimizers;
+        self
+    }
+
+    pub fn max_batch_size(mut self, max_batch_size: usize) -> EcConfigBuilder {
+        self.config.max_batch_size = max_batch_size;
+        self
+    }
+
+    pub fn max_l2(mut self, max_l2: f64) -> EcConfigBuilder {
+        self.config.max_l2 = max_l2;
+        self
+    }
+
+    pub fn gene_mutation_rate(mut self, gene_mutation_rate: f64) -> EcConfigBuilder {
+        self.config.gene_mutation_rate = gene_mutation_rate;
+        self
+    }
+
+    pub fn asexual_reproduction(mut self, asexual_reproduction: f64) -> EcConfigBuilder {
+        self.config.asexual_reproduction = asexual_reproduction;
+        self
+    }
+
+    pub fn sexual_reproduction(mut self, sexual_reproduction: f64) -> EcConfigBuilder {
+        self.config.sexual_reproduction = sexual_reproduction;
+        self
+    }
+
+    pub fn asexual_keep_rate(mut self, asexual_keep_rate: f64) -> EcConfigBuilder {
+        self.config.asexual_keep_rate = asexual_keep_rate;
+        self
+    }
+
+    pub fn sexual_keep_rate(mut self, sexual_keep_rate: f64) -> EcConfigBuilder {
+        self.config.sexual_keep_rate = sexual_keep_rate;
+        self
+    }
+
+    pub fn lamarckian(mut self, lamarckian: bool) -> EcConfigBuilder {
+        self.config.lamarckian = lamarckian;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> EcConfigBuilder {
+        self.config.threads = threads;
+        self
+    }
+
+    pub fn fitness_function(mut self, fitness_function: Arc<dyn FitnessFunction>) -> EcConfigBuilder {
+        self.config.fitness_function = fitness_function;
+        self
+    }
+
+    pub fn cost_scaler(mut self, cost_scaler: f64) -> EcConfigBuilder {
+        self.config.cost_scaler = cost_scaler;
+        self
+    }
+
+    pub fn search(mut self, search: SearchMode) -> EcConfigBuilder {
+        self.config.search = search;
+        self
+    }
+
+    pub fn selection(mut self, selection: SelectionConfig) -> EcConfigBuilder {
+        self.config.selection = selection;
+        self
+    }
+
+    pub fn elitism(mut self, elitism: usize) -> EcConfigBuilder {
+        self.config.elitism = elitism;
+        self
+    }
+
+    pub fn speciation(mut self, speciation: SpeciationConfig) -> EcConfigBuilder {
+        self.config.speciation = Some(speciation);
+        self
+    }
+
+    pub fn checkpoint(mut self, every: i32, path: &str) -> EcConfigBuilder {
+        self.config.checkpoint_every = every;
+        self.config.checkpoint_path = Some(path.to_string());
+        self
+    }
+
+    pub fn stats_path(mut self, path: &str) -> EcConfigBuilder {
+        self.config.stats_path = Some(path.to_string());
+        self
+    }
+
+    pub fn cache(mut self, cache: bool) -> EcConfigBuilder {
+        self.config.cache = cache;
+        self
+    }
+
+    pub fn cache_path(mut self, path: &str) -> EcConfigBuilder {
+        self.config.cache = true;
+        self.config.cache_path = Some(path.to_string());
+        self
+    }
+
+    pub fn hall_of_fame(mut self, size: usize, path: Option<&str>) -> EcConfigBuilder {
+        self.config.hall_of_fame_size = size;
+        self.config.hall_of_fame_path = path.map(|path| path.to_string());
+        self
+    }
+
+    pub fn build(self) -> Result<EcConfig, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_message(result: Result<EcConfig, ConfigError>) -> String {
+        match result {
+            Err(ConfigError::Invalid(message)) => message,
+            other => panic!("expected an invalid config, got {:?}", other),
+        }
+    }
+
+    // the fitness function can't be compared, it isn't part of the serialized config either
+    fn assert_same(a: &EcConfig, b: &EcConfig) {
+        assert_eq!(serde_json::to_value(a).unwrap(), serde_json::to_value(b).unwrap());
+    }
+
+    // writes `contents` to a temporary file with the given name and loads it
+    fn load(name: &str, contents: &str) -> Result<EcConfig, ConfigError> {
+        let dir = std::env::temp_dir().join(format!("nn_rust_config_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        let config = EcConfig::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn builder_sets_the_fields() {
+        let config = EcConfig::builder().seed(7).generations(3).population_size(12).max_layer_count(2).max_learning_rate(0.5).build().unwrap();
+        assert_same(&config, &EcConfig { seed: 7, generations: 3, population_size: 12, max_layer_count: 2, max_learning_rate: 0.5, ..EcConfig::default() });
+        assert!(EcConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn builder_rejects_invalid_values() {
+        assert_eq!(invalid_message(EcConfig::builder().population_size(0).build()), "population_size must be positive, got 0");
+        assert_eq!(invalid_message(EcConfig::builder().generations(-1).build()), "generations must not be negative, got -1");
+        assert_eq!(invalid_message(EcConfig::builder().min_epochs(0).build()), "min_epochs must be at least 1, got 0");
+        assert_eq!(invalid_message(EcConfig::builder().min_epochs(500).build()), "min_epochs (500) must be smaller than max_epochs_start (500)");
+        assert_eq!(invalid_message(EcConfig::builder().max_epochs(400).build()), "max_epochs_start (500) must not be larger than max_epochs (400)");
+        assert!(EcConfig::builder().max_calculations(100).build().is_err());
+        assert!(EcConfig::builder().max_layer_count(0).build().is_err());
+        assert!(EcConfig::builder().max_nodes_start(2000).build().is_err());
+        assert!(EcConfig::builder().max_learning_rate(0.0).build().is_err());
+        assert!(EcConfig::builder().max_learning_rate(f64::NAN).build().is_err());
+    }
+
+    #[test]
+    fn files_are_read_by_extension() {
+        let config = load("config.toml", "seed = 5\ngenerations = 2\n").unwrap();
+        assert_same(&config, &EcConfig { seed: 5, generations: 2, ..EcConfig::default() });
+        let config = load("config.json", r#"{"population_size": 8, "max_nodes": 50, "max_nodes_start": 50}"#).unwrap();
+        assert_same(&config, &EcConfig { population_size: 8, max_nodes: 50, max_nodes_start: 50, ..EcConfig::default() });
+
+        assert!(matches!(load("config.yaml", "seed: 5"), Err(ConfigError::UnknownFormat(_))));
+        assert!(matches!(load("config.toml", "seeds = 5"), Err(ConfigError::Toml(_))));
+        assert!(matches!(load("config.json", r#"{"seed": "five"}"#), Err(ConfigError::Json(_))));
+        assert_eq!(invalid_message(load("config.toml", "population_size = -3")), "population_size must be positive, got -3");
+        assert!(matches!(EcConfig::from_file("does/not/exist.toml"), Err(ConfigError::Io(_))));
+    }
+
+    #[test]
+    fn the_shipped_config_loads() {
+        let config = EcConfig::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/src/ec/ec_all_colors.toml")).unwrap();
+        assert_same(&config, &EcConfig::default());
+    }
+}
@@ -1,8 +1,11 @@
 use std::fmt;
-use crate::ec::evolutionary_computation::{INPUT_SIZE, OUTPUT_SIZE, TEST_SET, TRAINING_SET};
+use serde::{Deserialize, Serialize};
+use crate::ec::evaluation_context::EvaluationContext;
+use crate::ec::genome::Genome;
+use crate::nn::metrics::{argmax, Metrics};
 use crate::nn::neural_network::NeuralNetwork;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contestant {
     pub layer_count: i32,
     pub layer_sizes: Vec<i32>,
@@ -12,31 +15,50 @@ pub struct Contestant {
     pub calculations: i32,
     pub epochs: i32,
     pub learning_rate: f64,
+    pub genome: Genome,
+    // accuracy and errors in percent of the test samples
     pub accuracy: f64,
     pub average_error: f64,
     pub max_error: f64,
     pub max_error_index: usize,
+    // set by the fitness function of the EC after fit
     pub cost: f64,
     pub scaled_cost: f64,
     pub fitness: f64,
+    // of the test set, set by fit
+    pub metrics: Option<Metrics>,
     pub seed: i32,
     pub is_training: bool,
+    // set once fit has run, elites that are copied into the next generation keep their results
+    pub evaluated: bool,
+    // the results were taken from the evaluation cache, the network itself is untrained
+    pub cached: bool,
+    // front and crowding distance of the last NSGA-II survival
+    pub rank: usize,
+    pub crowding_distance: f64,
+    // of the last speciation, 0 without one
+    pub species: usize,
 }
 
 impl Contestant {
-    pub fn new(epochs: i32, seed: i32, layer_sizes: Vec<i32>, learning_rate: f64) -> Contestant {
+    pub fn new(epochs: i32, seed: i32, layer_sizes: Vec<i32>, learning_rate: f64, genome: Genome, context: &EvaluationContext) -> Contestant {
         let all_representations: (Vec<i32>, Vec<i32>) = Contestant::from_layer_sizes(layer_sizes.clone());
-        let layers: Vec<i32>;
-        unsafe { layers = Contestant::add_io(all_representations.1.clone(), INPUT_SIZE, OUTPUT_SIZE); }
+        let layers: Vec<i32> = Contestant::add_io(all_representations.1.clone(), context.input_size, context.output_size);
+        let mut neural_network = NeuralNetwork::new_with_activations(layers.iter().map(|x| *x as usize).collect(),
+                                                                     genome.layer_activations(&all_representations.0), learning_rate, seed);
+        neural_network.set_optimizer(genome.optimizer);
+        neural_network.batch_size = genome.batch_size;
+        neural_network.l2 = genome.l2;
         Contestant {
             layer_count: layer_sizes.len() as i32,
             layer_sizes: layer_sizes.clone(),
             layers: layers.clone(),
             lookup_table: all_representations.1,
-            neural_network: NeuralNetwork::new(layers.iter().map(|x| *x as usize).collect(), learning_rate, seed),
+            neural_network,
             calculations: Contestant::calculations_calculator(layers.clone()),
             epochs,
             learning_rate,
+            genome,
             accuracy: 0.0,
             average_error: 0.0,
             max_error: 0.0,
@@ -44,8 +66,14 @@ impl Contestant {
             cost: 0.0,
             scaled_cost: 0.0,
             fitness: 0.0,
+            metrics: None,
             seed,
             is_training: false,
+            evaluated: false,
+            cached: false,
+            rank: 0,
+            crowding_distance: 0.0,
+            species: 0,
         }
     }
 
@@ -83,67 +111,55 @@ impl Contestant {
         (lookup_table, layers)
     }
 
+    // the slot of every layer of the network, -1 for the input and layer_sizes.len() for the output layer
+    pub fn slots(&self) -> Vec<i32> {
+        let mut slots = vec![-1];
+        slots.extend(Contestant::from_layer_sizes(self.layer_sizes.clone()).0);
+        slots.push(self.layer_sizes.len() as i32);
+        slots
+    }
 
-    pub fn fit(&mut self, _iter: i32) {
-        unsafe {
-            dbg!(self.learning_rate);
-            self.neural_network.fit(&TRAINING_SET.clone().unwrap().inputs, &TRAINING_SET.clone().unwrap().targets, self.epochs);
-            // test the neural network
-            // TODO: fix this part, cause idk how the java code worked and its ugly anyways
-            let mut errors_per_color: Vec<Vec<f64>> = vec![vec![0.0; TEST_SET.clone().unwrap().targets[0].len()]; TEST_SET.clone().unwrap().inputs.len()];
-            //println!("layer_sizes: {:?}", self.neural_network.layer_sizes);
-            //println!("learning_rate: {:?}", self.neural_network.learning_rate);
-
-            let mut predictions: Vec<usize> = vec![0; TEST_SET.clone().unwrap().inputs.len()];
-            let mut outputs: Vec<Vec<f64>> = vec![vec![0.0; TEST_SET.clone().unwrap().targets[0].len()]; TEST_SET.clone().unwrap().inputs.len()];
-            for i in 0..TEST_SET.clone().unwrap().inputs.len() {
-                let output = self.neural_network.predict(TEST_SET.clone().unwrap().inputs[i].clone());
-                outputs[i] = output.clone();
-                //println!("output: {:?}", output);
-                let target = TEST_SET.clone().unwrap().targets[i].clone();
-                let mut correct_color= 0;
-                let mut predicted_color= 0;
-                for j in 0 .. output.len() {
-                    if target[j] == 1.0 {
-                        correct_color = j;
-                    }
-                    if output[j] > output[predicted_color] {
-                        predicted_color = j;
-                    }
-                }
-                if correct_color != predicted_color {
-                    errors_per_color[i][correct_color] += 1.0;
-                }
-                predictions[i] = predicted_color;
-            }
-            // println!("predictions: {:?}", predictions);
-            // dbg!(predictions);
-            // println!("outputs: {:?}", outputs);
-            /*
-            println!("errors_per_color:");
-            println!("{:?}", errors_per_color);
-
-            for i in errors_per_color.clone() {
-                println!("{:?}", i);
-            }*/
-            let mut errors_per_color_sum: Vec<f64> = vec![0.0; TEST_SET.clone().unwrap().targets[0].len()];
-            for i in 0..errors_per_color.len() {
-                for j in 0..errors_per_color[i].len() {
-                    errors_per_color_sum[j] += errors_per_color[i][j];
+    // Lamarckian inheritance, `sources` holds the parent of every slot (the last one is for the output layer).
+    // A layer takes the bias of its slot from that parent and, if the parent connects the same pair of slots,
+    // the weights feeding into it. Rows and columns that don't exist in the parent keep their initialization.
+    pub fn inherit_weights(&mut self, sources: &[&Contestant]) {
+        let slots = self.slots();
+        for i in 1..slots.len() {
+            let parent = sources[slots[i] as usize];
+            let parent_slots = parent.slots();
+            if let Some(j) = parent_slots.iter().position(|slot| *slot == slots[i]) {
+                self.neural_network.biases[i - 1].copy_overlap(&parent.neural_network.biases[j - 1]);
+                if parent_slots[j - 1] == slots[i - 1] {
+                    self.neural_network.weights[i - 1].copy_overlap(&parent.neural_network.weights[j - 1]);
                 }
             }
-            //println!("errors_per_color_sum: {:?}", errors_per_color_sum);
-            let (max_error_index, max_error) = errors_per_color_sum.iter().enumerate().max_by(|a, b| a.1.partial_cmp(b.1).unwrap()).unwrap();
-            let average_error = errors_per_color_sum.iter().sum::<f64>() / errors_per_color_sum.len() as f64;
-            let accuracy = 0.0; //tmp, TODO: add accuracy functionality here
-            //println!("max_error: {}, max_error_index: {}, average_error: {}, accuracy: {}", max_error, max_error_index, average_error, accuracy);
-            self.accuracy = accuracy;
-            self.average_error = average_error / TEST_SET.clone().unwrap().inputs.len() as f64 * 100.0;
-            self.max_error = *max_error / TEST_SET.clone().unwrap().inputs.len() as f64 * 100.0;
-            self.max_error_index = max_error_index;
-            self.cost = average_error + max_error;
         }
-        // println!("finished: {}", iter);
+    }
+
+    pub fn fit(&mut self, context: &EvaluationContext) {
+        let training_set = &context.training_set;
+        let test_set = &context.test_set;
+        self.neural_network.fit(&training_set.inputs, &training_set.targets, self.epochs);
+        // test the neural network
+        let metrics = self.neural_network.metrics(&test_set.inputs, &test_set.targets);
+        let samples = metrics.confusion.total() as f64;
+        // wrongly classified samples of every class
+        let errors: Vec<f64> = (0..metrics.confusion.classes()).map(|c| metrics.confusion.errors(c) as f64).collect();
+        let max_error_index = argmax(&errors);
+        let max_error = errors[max_error_index];
+        let average_error = errors.iter().sum::<f64>() / errors.len() as f64;
+        //println!("max_error: {}, max_error_index: {}, average_error: {}, accuracy: {}", max_error, max_error_index, average_error, metrics.accuracy);
+        self.accuracy = metrics.accuracy * 100.0;
+        self.average_error = average_error / samples * 100.0;
+        self.max_error = max_error / samples * 100.0;
+        self.max_error_index = max_error_index;
+        self.metrics = Some(metrics);
+        self.evaluated = true;
+    }
+
+    // minimized by the NSGA-II search
+    pub fn objectives(&self) -> Vec<f64> {
+        vec![self.cost, self.calculations as f64, self.epochs as f64]
     }
 
     pub fn print_properties(&self) {
@@ -155,6 +171,7 @@ impl Contestant {
         println!("\tcalculations: {}", self.calculations);
         println!("\tepochs: {}", self.epochs);
         println!("\tlearning_rate: {}", self.learning_rate);
+        println!("\tgenome: {:?}", self.genome);
         println!("\taccuracy: {}", self.accuracy);
         println!("\taverage_error: {}", self.average_error);
         println!("\tmax_error: {}", self.max_error);
@@ -168,6 +185,91 @@ impl Contestant {
 
 impl fmt::Display for Contestant {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Contestant {{layer_count: {}\n layer_sizes: {:?}\n layers: {:?}\n lookup_table: {:?}\n calculations: {}\n epochs: {}\n learning_rate: {}\n accuracy: {}\n average_error: {}\n max_error: {}\n cost: {}\n scaled_cost: {}\n fitness: {}\n seed: {}\n is_training: {} }}", self.layer_count, self.layer_sizes, self.layers, self.lookup_table, self.calculations, self.epochs, self.learning_rate, self.accuracy, self.average_error, self.max_error, self.cost, self.scaled_cost, self.fitness, self.seed, self.is_training)
+        write!(f, "Contestant {{layer_count: {}\n layer_sizes: {:?}\n layers: {:?}\n lookup_table: {:?}\n calculations: {}\n epochs: {}\n learning_rate: {}\n genome: {:?}\n accuracy: {}\n average_error: {}\n max_error: {}\n cost: {}\n scaled_cost: {}\n fitness: {}\n seed: {}\n is_training: {} }}", self.layer_count, self.layer_sizes, self.layers, self.lookup_table, self.calculations, self.epochs, self.learning_rate, self.genome, self.accuracy, self.average_error, self.max_error, self.cost, self.scaled_cost, self.fitness, self.seed, self.is_training)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::activation::Activation;
+    use crate::nn::data_set::DataSet;
+    use crate::nn::optimizer::OptimizerConfig;
+
+    fn context() -> EvaluationContext {
+        let data_set = DataSet::new(vec![vec![0.0, 1.0]], vec![vec![1.0, 0.0, 0.0]]);
+        EvaluationContext::new(data_set.clone(), data_set, 2, 3)
+    }
+
+    fn contestant(layer_sizes: Vec<i32>, seed: i32, context: &EvaluationContext) -> Contestant {
+        let genome = Genome { activations: vec![Activation::Sigmoid; 3], output_activation: Activation::Sigmoid, optimizer: OptimizerConfig::default(), batch_size: 1, l2: 0.0 };
+        Contestant::new(10, seed, layer_sizes, 0.01, genome, context)
+    }
+
+    // the parent's weights are moved away from any initialization, as if it was trained
+    fn trained_parent(context: &EvaluationContext) -> Contestant {
+        let mut parent = contestant(vec![8, 0, 9], 1, context);
+        for m in parent.neural_network.weights.iter_mut().chain(parent.neural_network.biases.iter_mut()) {
+            m.matrix.apply(|x| *x += 10.0);
+        }
+        parent
+    }
+
+    // a fresh child and the same child after inheriting from `parent` for every slot
+    fn fresh_and_inherited(layer_sizes: Vec<i32>, parent: &Contestant, context: &EvaluationContext) -> (NeuralNetwork, NeuralNetwork) {
+        let fresh = contestant(layer_sizes.clone(), 2, context);
+        let mut child = contestant(layer_sizes, 2, context);
+        child.inherit_weights(&[parent; 4]);
+        (fresh.neural_network, child.neural_network)
+    }
+
+    #[test]
+    fn slots_of_the_layers() {
+        let context = context();
+        assert_eq!(contestant(vec![8, 0, 9], 1, &context).slots(), vec![-1, 0, 2, 3]);
+        assert_eq!(contestant(vec![0, 0, 0], 1, &context).slots(), vec![-1, 3]);
+    }
+
+    #[test]
+    fn same_layout_inherits_everything() {
+        let context = context();
+        let parent = trained_parent(&context);
+        let (_, child) = fresh_and_inherited(vec![8, 0, 9], &parent, &context);
+        for i in 0..3 {
+            assert_eq!(child.weights[i].matrix, parent.neural_network.weights[i].matrix);
+            assert_eq!(child.biases[i].matrix, parent.neural_network.biases[i].matrix);
+        }
+    }
+
+    #[test]
+    fn resized_layers_keep_the_overlap_and_initialize_the_rest() {
+        let context = context();
+        let parent = trained_parent(&context);
+        // slot 2 grows from 9 to 11 nodes
+        let (fresh, child) = fresh_and_inherited(vec![8, 0, 11], &parent, &context);
+        assert_eq!(child.weights[0].matrix, parent.neural_network.weights[0].matrix);
+        let (grown, parent_weights) = (&child.weights[1].matrix, &parent.neural_network.weights[1].matrix);
+        assert_eq!(grown.rows(0, 9), parent_weights.rows(0, 9));
+        assert_eq!(grown.rows(9, 2), fresh.weights[1].matrix.rows(9, 2));
+        assert_eq!(child.biases[1].matrix.rows(0, 9), parent.neural_network.biases[1].matrix.rows(0, 9));
+        assert_eq!(child.biases[1].matrix.rows(9, 2), fresh.biases[1].matrix.rows(9, 2));
+        // the output layer gets two new inputs
+        assert_eq!(child.weights[2].matrix.columns(0, 9), parent.neural_network.weights[2].matrix.columns(0, 9));
+        assert_eq!(child.weights[2].matrix.columns(9, 2), fresh.weights[2].matrix.columns(9, 2));
+    }
+
+    #[test]
+    fn new_connections_are_initialized() {
+        let context = context();
+        let parent = trained_parent(&context);
+        // slot 1 is new and slot 2 is now fed by slot 1 instead of slot 0
+        let (fresh, child) = fresh_and_inherited(vec![8, 7, 9], &parent, &context);
+        assert_eq!(child.weights[0].matrix, parent.neural_network.weights[0].matrix);
+        assert_eq!(child.weights[1].matrix, fresh.weights[1].matrix);
+        assert_eq!(child.biases[1].matrix, fresh.biases[1].matrix);
+        assert_eq!(child.weights[2].matrix, fresh.weights[2].matrix);
+        // the bias belongs to the slot, not to the connection
+        assert_eq!(child.biases[2].matrix, parent.neural_network.biases[1].matrix);
+        assert_eq!(child.weights[3].matrix, parent.neural_network.weights[2].matrix);
+    }
+}
@@ -0,0 +1,35 @@
+use crate::ec::cache::data_fingerprint;
+use crate::nn::data_set::DataSet;
+
+// everything contestants need to build and evaluate their networks,
+// shared (read only) between all contestants of one EvolutionaryComputation
+#[derive(Debug)]
+pub struct EvaluationContext {
+    pub training_set: DataSet,
+    pub test_set: DataSet,
+    pub input_size: i32,
+    pub output_size: i32,
+    // hash of both data sets, part of the keys of the evaluation cache
+    pub fingerprint: u64,
+}
+
+impl EvaluationContext {
+    pub fn new(training_set: DataSet, test_set: DataSet, input_size: i32, output_size: i32) -> EvaluationContext {
+        let fingerprint = data_fingerprint(&[&training_set, &test_set]);
+        EvaluationContext { training_set, test_set, input_size, output_size, fingerprint }
+    }
+
+    // input and output sizes are taken from the training set, the test set has to match them
+    pub fn from_data_sets(training_set: DataSet, test_set: DataSet) -> Result<EvaluationContext, String> {
+        if training_set.inputs.is_empty() || test_set.inputs.is_empty() {
+            return Err("training and test set must not be empty".to_string());
+        }
+        let input_size = training_set.inputs[0].len() as i32;
+        let output_size = training_set.targets[0].len() as i32;
+        if test_set.inputs[0].len() as i32 != input_size || test_set.targets[0].len() as i32 != output_size {
+            return Err(format!("training set has {} inputs and {} outputs, test set has {} and {}",
+                               input_size, output_size, test_set.inputs[0].len(), test_set.targets[0].len()));
+        }
+        Ok(EvaluationContext::new(training_set, test_set, input_size, output_size))
+    }
+}
@@ -1,74 +1,162 @@
-use std::thread;
-use rand::{Rng, thread_rng};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use rand::Rng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use crate::ec::cache::{genome_hash, EvaluationCache};
+use crate::ec::checkpoint::{Checkpoint, CheckpointError};
+use crate::ec::config::{ConfigError, EcConfig, SearchMode};
 use crate::ec::contestant::Contestant;
+use crate::ec::evaluation_context::EvaluationContext;
+use crate::ec::fitness::FitnessFunction;
+use crate::ec::genome::Genome;
+use crate::ec::hall_of_fame::HallOfFame;
+use crate::ec::nsga2;
+use crate::ec::random::{derive_seed, stream_rng};
+use crate::ec::stats::{stats_writer, GenerationObserver, GenerationStats};
+use crate::ec::selection::SelectionStrategy;
 use crate::nn::data_set::DataSet;
+use crate::nn::model::ModelFormat;
+use crate::nn::optimizer::OptimizerConfig;
 
-const COST_SCALER: f64 = 1.2;
 // const ERROR_SCALER: f64 = 2.5;
 // const ACCURACY_SCALER: f64 = 1.2;
 // const CALCULATIONS_SCALER: f64 = 0.2;
 // const EPOCHS_SCALER: f64 = 0.2;
 // const PARAMETER_CHANGE_RATE_EXP: i32 = 5;
 // const PARAMETER_CHANGE_RATE_LINEAR: i32 = 1;
-const EXP_PARAMETER_CHANGE_RATE_EXP: i32 = 5;
-const EXP_PARAMETER_CHANGE_RATE_LINEAR: i32 = 10;
-const ASEXUAL_REPRODUCTION: f64 = 0.75;
-const SEXUAL_REPRODUCTION: f64 = 0.22;
-
 //asexual reproduction
 //(-1 .. 1 rand) ** change_rate * (max - min) + min
-const ASEXUAL_KEEP_RATE: f64 = 0.95;
-//sexual reproduction
-//how much to keep from both parents - the rest is mixed together
-const SEXUAL_KEEP_RATE: f64 = 0.3;
-
-pub static mut TRAINING_SET: Option<DataSet> = None;
-pub static mut TEST_SET: Option<DataSet> = None;
-pub static mut OUTPUT_SIZE: i32 = 0;
-pub static mut INPUT_SIZE: i32 = 0;
+const EXP_PARAMETER_CHANGE_RATE_EXP: i32 = 5;
+const EXP_PARAMETER_CHANGE_RATE_LINEAR: i32 = 10;
+// upper bounds of the optimizer hyperparameters while mutating
+const MAX_MOMENTUM: f64 = 0.99;
+const MAX_DECAY: f64 = 0.999;
+const MAX_BETA1: f64 = 0.99;
+const MAX_BETA2: f64 = 0.9999;
+const MAX_WEIGHT_DECAY: f64 = 0.1;
+// genes of the genome move by up to this share of their range, smaller steps are more likely
+const GENE_STEP: f64 = 0.5;
+const GENE_STEP_EXP: i32 = 3;
 
 pub struct EvolutionaryComputation {
+    context: Arc<EvaluationContext>,
+    config: EcConfig,
+    pool: Arc<ThreadPool>,
+    selection: Box<dyn SelectionStrategy>,
     population: Vec<Contestant>,
     current_generation: i32,
-    population_size: i32,
-    max_epochs: i32,
-    max_epochs_start: i32,
-    min_epochs: i32,
-    max_calculations: i32,
-    max_calculations_start: i32,
-    max_layer_count: i32,
-    max_nodes: i32,
-    max_nodes_start: i32,
-    max_learning_rate: f64,
-    generations: i32,
-    seed: i32,
+    observers: Vec<Box<dyn GenerationObserver>>,
+    cache: EvaluationCache,
+    hall_of_fame: HallOfFame,
+    stats_log: Option<Box<dyn GenerationObserver>>,
+    generation_start: Instant,
+}
+
+// what run returns
+#[derive(Debug, Clone)]
+pub struct RunResult {
+    pub generations: i32,
+    pub hall_of_fame: HallOfFame,
+    // the non dominated contestants of the last generation with trained networks, empty unless the search is NSGA-II
+    pub pareto_front: Vec<Contestant>,
 }
 
 impl EvolutionaryComputation {
-    pub fn new(training_set: DataSet, seed: i32, generations: i32, population_size: i32, input_size: i32, output_size: i32, max_epochs: i32, max_epochs_start: i32, max_calculations: i32, max_calculations_start: i32, max_layer_count: i32, max_nodes: i32, max_nodes_start: i32, max_learning_rate: f64, min_epochs: i32, test_set: DataSet) -> EvolutionaryComputation {
-        unsafe {
-            TRAINING_SET = Option::from(training_set);
-            TEST_SET = Option::from(test_set);
-            INPUT_SIZE = input_size;
-            OUTPUT_SIZE = output_size;
-        }
-        EvolutionaryComputation {
+    // input and output sizes are taken from the training set
+    pub fn new(config: EcConfig, training_set: DataSet, test_set: DataSet) -> Result<EvolutionaryComputation, ConfigError> {
+        let context = EvaluationContext::from_data_sets(training_set, test_set).map_err(ConfigError::DataSets)?;
+        EvolutionaryComputation::with_context(config, Arc::new(context))
+    }
+
+    // several runs (like the islands of an IslandModel) can share the data sets
+    pub fn with_context(config: EcConfig, context: Arc<EvaluationContext>) -> Result<EvolutionaryComputation, ConfigError> {
+        let pool = ThreadPoolBuilder::new().num_threads(config.threads).build()?;
+        EvolutionaryComputation::with_pool(config, context, Arc::new(pool))
+    }
+
+    // runs that don't evaluate at the same time can share their workers as well, config.threads is ignored
+    pub fn with_pool(config: EcConfig, context: Arc<EvaluationContext>, pool: Arc<ThreadPool>) -> Result<EvolutionaryComputation, ConfigError> {
+        config.validate()?;
+        Ok(EvolutionaryComputation {
+            context,
+            selection: config.selection.build(),
+            hall_of_fame: HallOfFame::new(config.hall_of_fame_size),
+            config,
+            pool,
             population: Vec::new(),
             current_generation: 0,
-            population_size,
-            max_epochs,
-            max_epochs_start,
-            min_epochs,
-            max_calculations,
-            max_calculations_start,
-            max_layer_count,
-            max_nodes,
-            max_nodes_start,
-            max_learning_rate,
-            generations,
-            seed,
+            observers: Vec::new(),
+            cache: EvaluationCache::new(),
+            stats_log: None,
+            generation_start: Instant::now(),
+        })
+    }
+
+    pub fn config(&self) -> &EcConfig {
+        &self.config
+    }
+
+    pub fn context(&self) -> Arc<EvaluationContext> {
+        self.context.clone()
+    }
+
+    // the generation that gets evaluated next
+    pub fn current_generation(&self) -> i32 {
+        self.current_generation
+    }
+
+    // continues a run from a checkpoint, the data sets have to be the ones the run started with
+    // and the fitness function has to be the one it used (the same name)
+    pub fn resume<P: AsRef<Path>>(path: P, training_set: DataSet, test_set: DataSet, fitness_function: Arc<dyn FitnessFunction>) -> Result<EvolutionaryComputation, CheckpointError> {
+        let mut checkpoint = Checkpoint::load(path)?;
+        if fitness_function.name() != checkpoint.fitness_function {
+            return Err(CheckpointError::Invalid(format!("the run used the fitness function {}, got {}", checkpoint.fitness_function, fitness_function.name())));
+        }
+        checkpoint.config.fitness_function = fitness_function;
+        checkpoint.config.validate().map_err(|e| CheckpointError::Invalid(e.to_string()))?;
+        if checkpoint.population.is_empty() {
+            return Err(CheckpointError::Invalid("the population is empty".to_string()));
+        }
+        let mut ec = EvolutionaryComputation::new(checkpoint.config, training_set, test_set).map_err(|e| CheckpointError::Invalid(e.to_string()))?;
+        if ec.context.fingerprint != checkpoint.fingerprint {
+            return Err(CheckpointError::Invalid("the data sets are not the ones the run was started with".to_string()));
+        }
+        for contestant in &checkpoint.population {
+            let layers = &contestant.layers;
+            if layers.first() != Some(&ec.context.input_size) || layers.last() != Some(&ec.context.output_size) {
+                return Err(CheckpointError::Invalid(format!("contestant with layers {:?} doesn't fit the data sets ({} inputs, {} outputs)",
+                                                            layers, ec.context.input_size, ec.context.output_size)));
+            }
+        }
+        ec.population = checkpoint.population;
+        ec.current_generation = checkpoint.generation;
+        ec.hall_of_fame = checkpoint.hall_of_fame;
+        Ok(ec)
+    }
+
+    pub fn set_fitness_function(&mut self, fitness_function: Arc<dyn FitnessFunction>) {
+        self.config.fitness_function = fitness_function;
+    }
+
+    // gets the statistics of every generation
+    pub fn add_observer(&mut self, observer: Box<dyn GenerationObserver>) {
+        self.observers.push(observer);
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            config: self.config.clone(),
+            generation: self.current_generation,
+            population: self.population.clone(),
+            hall_of_fame: self.hall_of_fame.clone(),
+            fitness_function: self.config.fitness_function.name(),
+            fingerprint: self.context.fingerprint,
         }
     }
+
     pub fn get_best(population: &Vec<Contestant>) -> Contestant {
         let mut best = population[0].clone();
         for i in 1..population.len() {
@@ -88,124 +176,356 @@ impl EvolutionaryComputation {
         population[worst].clone()
     }
 
-    pub fn run(&mut self) {
-        let mut new_population: Vec<Contestant>;
-        for _i in 0..self.population_size {
-            self.population.push(self.generate_random_start());
-        }
-        for i in 0..self.generations as usize {
-            println!("Start Gen #{}", i);
-            let start = std::time::Instant::now();
-            self.current_generation = i as i32;
-            // TODO: thread magic
-            let mut todo = vec![];
-            let mut pop = self.population.clone();
-            for j in 0..self.population_size as usize {
-                let iter = j.clone(); //mb needs a .clone()
-                let mut pop_j = pop[j].clone();
-                todo.push(thread::spawn(move || {
-                    pop_j.fit(iter as i32);
-                    println!("thread finished {}: error: {}|{}, epochs: {}, layers: {:?}", iter, pop_j.average_error, pop_j.max_error, pop_j.epochs, pop_j.layers);
-                    (pop_j, iter)
-                }));
+    pub fn run(&mut self) -> RunResult {
+        self.start();
+        while !self.is_finished() {
+            self.evaluate();
+            self.advance();
+        }
+        self.finish()
+    }
+
+    // creates the random start (a resumed run already has its population), opens the statistics log
+    // and loads the cache, run does this itself, it's only needed when stepping through the generations
+    pub fn start(&mut self) {
+        if self.population.is_empty() {
+            let seed = self.generation_seed(0);
+            for k in 0..self.config.population_size as u64 {
+                self.population.push(self.generate_random_start(&mut stream_rng(seed, k)));
             }
-            for t in todo {
-                let res = t.join().unwrap();
-                pop[res.1 as usize] = res.0;
+        }
+        // a resumed run continues its log
+        if self.stats_log.is_none() {
+            if let Some(path) = &self.config.stats_path {
+                match stats_writer(path, self.current_generation > 0) {
+                    Ok(writer) => self.stats_log = Some(writer),
+                    Err(e) => println!("Could not open {}: {}", path, e),
+                }
+            }
+        }
+        if let Some(path) = &self.config.cache_path {
+            if self.cache.is_empty() && Path::new(path).exists() {
+                match EvaluationCache::load(path) {
+                    Ok(cache) => self.cache = cache,
+                    Err(e) => println!("Could not load the cache from {}: {}", path, e),
+                }
             }
-            self.population = pop;
-            // stop timer
-            let duration = start.elapsed();
-            println!("Time elapsed Gen {} is: {:?}", i, duration);
-            println!("Generation: {}", i);
-            println!("Best: {}", EvolutionaryComputation::get_best(&self.population).average_error);
-            println!("Worst: {}", EvolutionaryComputation::get_worst(&self.population).average_error);
-            println!("Best Contestant: \n {}", EvolutionaryComputation::get_best(&self.population));
-            new_population = self.next_gen(&mut self.population.clone());
-            self.population = new_population;
         }
     }
 
-    pub fn generate_random_start(&self) -> Contestant {
-        let mut layer_sizes_: Vec<i32> = Vec::new();
-        for _i in 0..self.max_layer_count {
-            layer_sizes_.push(thread_rng().gen_range(0..self.max_nodes_start));
+    pub fn is_finished(&self) -> bool {
+        self.current_generation >= self.config.generations
+    }
+
+    // trains and scores every contestant of the current generation that doesn't have results yet
+    pub fn evaluate(&mut self) {
+        let i = self.current_generation;
+        println!("Start Gen #{}", i);
+        self.generation_start = Instant::now();
+        let context: &EvaluationContext = &self.context;
+        let fitness_function = &self.config.fitness_function;
+        if self.config.cache {
+            let mut hits = 0;
+            for contestant in self.population.iter_mut().filter(|c| !c.evaluated) {
+                if self.cache.restore(genome_hash(contestant, context), contestant) {
+                    contestant.cost = fitness_function.cost(contestant);
+                    hits += 1;
+                }
+            }
+            println!("Cache hits: {}", hits);
+        }
+        let population = &mut self.population;
+        // one task per contestant, so idle workers can steal the ones that are still queued
+        self.pool.install(|| {
+            population.par_iter_mut().with_max_len(1).enumerate().filter(|(_, c)| !c.evaluated).for_each(|(j, contestant)| {
+                contestant.fit(context);
+                contestant.cost = fitness_function.cost(contestant);
+                println!("thread finished {}: error: {}|{}, epochs: {}, layers: {:?}", j, contestant.average_error, contestant.max_error, contestant.epochs, contestant.layers);
+            });
+        });
+        if self.config.cache {
+            for contestant in &self.population {
+                self.cache.insert(genome_hash(contestant, context), contestant);
+            }
+            if let Some(path) = &self.config.cache_path {
+                if let Err(e) = self.cache.save(path) {
+                    println!("Could not write the cache to {}: {}", path, e);
+                }
+            }
+        }
+        for contestant in self.population.iter().filter(|c| c.evaluated) {
+            let key = genome_hash(contestant, context);
+            if self.hall_of_fame.accepts(key, contestant.cost) {
+                let mut contestant = contestant.clone();
+                // its network is still untrained, training is deterministic so this gives the one the results came from
+                if contestant.cached {
+                    contestant.fit(context);
+                    contestant.cached = false;
+                }
+                self.hall_of_fame.insert(key, contestant);
+            }
+        }
+        // stop timer
+        let duration = self.generation_start.elapsed();
+        println!("Time elapsed Gen {} is: {:?}", i, duration);
+    }
+
+    // breeds the next generation from the evaluated one, reports its statistics and writes the checkpoint
+    pub fn advance(&mut self) {
+        let i = self.current_generation;
+        println!("Generation: {}", i);
+        println!("Best: {}", EvolutionaryComputation::get_best(&self.population).average_error);
+        println!("Worst: {}", EvolutionaryComputation::get_worst(&self.population).average_error);
+        println!("Best Contestant: \n {}", EvolutionaryComputation::get_best(&self.population));
+        let mut population = std::mem::take(&mut self.population);
+        let new_population = match self.config.search {
+            SearchMode::SingleObjective => self.next_gen(&mut population, i + 1),
+            SearchMode::Nsga2 => self.next_gen_nsga2(&mut population, i + 1),
+        };
+        let stats = GenerationStats::new(i, &population, self.generation_start.elapsed());
+        for observer in self.observers.iter_mut().chain(self.stats_log.iter_mut()) {
+            observer.observe(&stats);
         }
-        unsafe {
-            let from_layer = Contestant::from_layer_sizes(layer_sizes_.clone());
-            while Contestant::calculations_calculator(Contestant::add_io(Contestant::from_layer_sizes(layer_sizes_.clone()).1, INPUT_SIZE, OUTPUT_SIZE)) > self.max_calculations_start {
-                layer_sizes_[from_layer.0[thread_rng().gen_range(0..from_layer.0.len() - 1)] as usize] = 0;
+        self.population = new_population;
+        self.current_generation = i + 1;
+        if self.config.checkpoint_every > 0 && self.current_generation % self.config.checkpoint_every == 0 {
+            if let Some(path) = &self.config.checkpoint_path {
+                match self.checkpoint().save(path) {
+                    Ok(()) => println!("Checkpoint of Gen {} written to {}", self.current_generation, path),
+                    Err(e) => println!("Could not write checkpoint to {}: {}", path, e),
+                }
+            }
+        }
+    }
+
+    // saves the hall of fame if there is a hall_of_fame_path
+    pub fn finish(&mut self) -> RunResult {
+        if let Some(path) = &self.config.hall_of_fame_path {
+            match self.hall_of_fame.save(path, ModelFormat::Json) {
+                Ok(()) => println!("Hall of fame written to {}", path),
+                Err(e) => println!("Could not write the hall of fame to {}: {}", path, e),
             }
         }
+        let pareto_front = match self.config.search {
+            SearchMode::SingleObjective => Vec::new(),
+            SearchMode::Nsga2 => self.pareto_front().into_iter().map(|mut contestant| {
+                // like for the hall of fame, the network has to be trained
+                if contestant.cached {
+                    contestant.fit(&self.context);
+                    contestant.cached = false;
+                }
+                contestant
+            }).collect(),
+        };
+        RunResult {
+            generations: self.current_generation,
+            hall_of_fame: self.hall_of_fame.clone(),
+            pareto_front,
+        }
+    }
+
+    // generation 0 is the random start, the population of generation g is bred with the seed of g
+    pub fn generation_seed(&self, generation: i32) -> u64 {
+        derive_seed(self.config.seed as u64, generation as u64)
+    }
+
+    pub fn generate_random_start(&self, rng: &mut StdRng) -> Contestant {
+        let mut layer_sizes_: Vec<i32> = Vec::new();
+        for _i in 0..self.config.max_layer_count {
+            layer_sizes_.push(rng.gen_range(0..self.config.max_nodes_start));
+        }
+        let from_layer = Contestant::from_layer_sizes(layer_sizes_.clone());
+        while Contestant::calculations_calculator(Contestant::add_io(Contestant::from_layer_sizes(layer_sizes_.clone()).1, self.context.input_size, self.context.output_size)) > self.config.max_calculations_start {
+            layer_sizes_[from_layer.0[rng.gen_range(0..from_layer.0.len() - 1)] as usize] = 0;
+        }
         let lookup: Vec<i32> = Contestant::from_layer_sizes(layer_sizes_.clone()).0;
-        let keep_rate: f64 = thread_rng().gen_range(0.0..1.0);
+        let keep_rate: f64 = rng.gen_range(0.0..1.0);
 
         for i in lookup {
-            if thread_rng().gen_range(0.0..1.0) < keep_rate {
+            if rng.gen_range(0.0..1.0) < keep_rate {
                 layer_sizes_[i as usize] = 0;
             }
         }
-        Contestant::new(thread_rng().gen_range(self.min_epochs..self.max_epochs_start),
-                        self.seed, layer_sizes_, thread_rng().gen_range(0.0..self.max_learning_rate))
+        Contestant::new(rng.gen_range(self.config.min_epochs..self.config.max_epochs_start),
+                        rng.gen_range(0..i32::MAX), layer_sizes_, rng.gen_range(0.0..self.config.max_learning_rate), self.random_genome(rng), &self.context)
     }
 
-    pub fn next_gen(&self, population: &mut Vec<Contestant>) -> Vec<Contestant> {
+    pub fn random_genome(&self, rng: &mut StdRng) -> Genome {
+        let config = &self.config;
+        Genome {
+            activations: (0..config.max_layer_count).map(|_| config.hidden_activations[rng.gen_range(0..config.hidden_activations.len())]).collect(),
+            output_activation: config.output_activations[rng.gen_range(0..config.output_activations.len())],
+            optimizer: config.optimizers[rng.gen_range(0..config.optimizers.len())],
+            batch_size: rng.gen_range(1..=config.max_batch_size),
+            l2: if config.max_l2 > 0.0 { rng.gen_range(0.0..config.max_l2) } else { 0.0 },
+        }
+    }
+
+    // parents are selected with their own random stream and every child gets one more,
+    // so a child doesn't depend on how many numbers its siblings used
+    pub fn next_gen(&self, population: &mut Vec<Contestant>, generation: i32) -> Vec<Contestant> {
+        let seed = self.generation_seed(generation);
         // println!("Next Gen");
         let mut next_population: Vec<Contestant> = Vec::new();
-        for i in 0..self.population_size as usize {
-            population[i].scaled_cost = population[i].cost.powf(COST_SCALER);
+        for i in 0..self.config.population_size as usize {
+            population[i].scaled_cost = population[i].cost.powf(self.config.cost_scaler);
         }
         let worst_contestant = EvolutionaryComputation::get_worst(&population.clone());
-        let mut fitness_sum: f64 = 0.0;
-        for i in 0..self.population_size as usize {
+        for i in 0..self.config.population_size as usize {
             population[i].fitness = worst_contestant.scaled_cost / population[i].scaled_cost;
-            fitness_sum += population[i].fitness;
         }
-        // for i in 0..self.population_size as usize {
+        // for i in 0..self.config.population_size as usize {
         //     population[i].print_properties();
         // }
-        for _i in 0..population.len() {
-            // println!("pop len: {}", population.len());
-            let point = self.contestant_int(population, fitness_sum);
-            let r_number: f64 = thread_rng().gen_range(0.0..1.0);
-            if r_number < ASEXUAL_REPRODUCTION {
-                next_population.push(self.mutate(&mut population[point].clone()));
-            } else if r_number < SEXUAL_REPRODUCTION + ASEXUAL_REPRODUCTION {
-                let point2 = self.contestant_int(population, fitness_sum);
-                next_population.push(self.mutate(&mut self.sexual_reproduction(&population[point], &population[point2])));
-            } else {
-                next_population.push(self.generate_random_start());
+        let mut order: Vec<usize> = (0..population.len()).collect();
+        order.sort_by(|a, b| population[*b].fitness.total_cmp(&population[*a].fitness));
+        for i in order.iter().take(self.config.elitism) {
+            next_population.push(population[*i].clone());
+        }
+
+        let children = population.len() - next_population.len();
+        let mut fitness: Vec<f64> = population.iter().map(|c| c.fitness).collect();
+        // fitness sharing only affects the selection of the parents, the elites are the best by their own fitness
+        if let Some(speciation) = &self.config.speciation {
+            let species = speciation.species(population, &self.config);
+            let mut sizes = vec![0; species.iter().max().map_or(0, |s| s + 1)];
+            for s in &species {
+                sizes[*s] += 1;
+            }
+            for ((contestant, fitness), s) in population.iter_mut().zip(fitness.iter_mut()).zip(species) {
+                contestant.species = s;
+                *fitness /= sizes[s] as f64;
             }
         }
+        // two parents per child, the second one is only used for sexual reproduction
+        let parents = self.selection.select(&fitness, 2 * children, &mut stream_rng(seed, 0));
+        for k in 0..children {
+            // println!("pop len: {}", population.len());
+            let rng = &mut stream_rng(seed, k as u64 + 1);
+            let (point, point2) = (parents[2 * k], parents[2 * k + 1]);
+            next_population.push(self.breed(&population[point], &population[point2], rng));
+        }
         next_population
     }
 
-    pub fn contestant_int(&self, population: &Vec<Contestant>, fitness_sum: f64) -> usize {
-        let mut current_sum = population[0].fitness;
-        let mut point = 0;
-        let goal = thread_rng().gen_range(0.0..fitness_sum);
-        while current_sum < goal {
-            point += 1;
-            current_sum += population[point].fitness;
+    // NSGA-II (mu + lambda): the evaluated parents and offspring in `population` are sorted into fronts,
+    // the best population_size by front and crowding distance survive and breed as many new offspring,
+    // the next population holds both and only the offspring get trained
+    pub fn next_gen_nsga2(&self, population: &mut [Contestant], generation: i32) -> Vec<Contestant> {
+        let seed = self.generation_seed(generation);
+        let size = self.config.population_size as usize;
+        let objectives: Vec<Vec<f64>> = population.iter().map(|c| c.objectives()).collect();
+        let mut survivors: Vec<Contestant> = Vec::new();
+        for (rank, front) in nsga2::non_dominated_sort(&objectives).iter().enumerate() {
+            let distances = nsga2::crowding_distance(&objectives, front);
+            for (i, distance) in front.iter().zip(&distances) {
+                population[*i].rank = rank;
+                population[*i].crowding_distance = *distance;
+                // used by the mutation, the first front changes the least
+                population[*i].fitness = 1.0 / (rank + 1) as f64;
+            }
+            if survivors.len() < size {
+                let mut order: Vec<usize> = (0..front.len()).collect();
+                order.sort_by(|a, b| distances[*b].total_cmp(&distances[*a]));
+                for i in order.into_iter().take(size - survivors.len()) {
+                    survivors.push(population[front[i]].clone());
+                }
+            }
+        }
+
+        let ranks: Vec<usize> = survivors.iter().map(|c| c.rank).collect();
+        let distances: Vec<f64> = survivors.iter().map(|c| c.crowding_distance).collect();
+        let selection_rng = &mut stream_rng(seed, 0);
+        let mut offspring: Vec<Contestant> = Vec::new();
+        for k in 0..size {
+            let point = nsga2::crowded_tournament(&ranks, &distances, selection_rng);
+            let point2 = nsga2::crowded_tournament(&ranks, &distances, selection_rng);
+            offspring.push(self.breed(&survivors[point], &survivors[point2], &mut stream_rng(seed, k as u64 + 1)));
+        }
+        survivors.extend(offspring);
+        survivors
+    }
+
+    // the evaluated contestants that no other evaluated contestant dominates
+    pub fn pareto_front(&self) -> Vec<Contestant> {
+        EvolutionaryComputation::non_dominated(&self.population)
+    }
+
+    pub fn non_dominated(contestants: &[Contestant]) -> Vec<Contestant> {
+        let evaluated: Vec<&Contestant> = contestants.iter().filter(|c| c.evaluated).collect();
+        let objectives: Vec<Vec<f64>> = evaluated.iter().map(|c| c.objectives()).collect();
+        match nsga2::non_dominated_sort(&objectives).first() {
+            Some(front) => front.iter().map(|i| evaluated[*i].clone()).collect(),
+            None => Vec::new(),
         }
-        point
     }
 
-    pub fn sexual_reproduction(&self, a: &Contestant, b: &Contestant) -> Contestant {
+    // copies of the evaluated contestants with the lowest cost, they migrate to other islands
+    pub fn emigrants(&self, count: usize) -> Vec<Contestant> {
+        let mut evaluated: Vec<&Contestant> = self.population.iter().filter(|c| c.evaluated).collect();
+        evaluated.sort_by(|a, b| a.cost.total_cmp(&b.cost));
+        evaluated.into_iter().take(count).cloned().collect()
+    }
+
+    // the immigrants replace the contestants with the highest cost, their costs
+    // are recalculated with the fitness function of this run
+    pub fn immigrate(&mut self, immigrants: Vec<Contestant>) {
+        let population = &mut self.population;
+        let cost = |c: &Contestant| if c.evaluated { c.cost } else { f64::INFINITY };
+        let mut order: Vec<usize> = (0..population.len()).collect();
+        order.sort_by(|a, b| cost(&population[*b]).total_cmp(&cost(&population[*a])));
+        for (i, mut immigrant) in order.into_iter().zip(immigrants) {
+            immigrant.cost = self.config.fitness_function.cost(&immigrant);
+            population[i] = immigrant;
+        }
+    }
+
+    // a mutated copy of `a`, a mutated mix of `a` and `b` or a new random contestant
+    fn breed(&self, a: &Contestant, b: &Contestant, rng: &mut StdRng) -> Contestant {
+        let r_number: f64 = rng.gen_range(0.0..1.0);
+        if r_number < self.config.asexual_reproduction {
+            self.mutate(&mut a.clone(), rng)
+        } else if r_number < self.config.sexual_reproduction + self.config.asexual_reproduction {
+            let mut child = self.sexual_reproduction(a, b, rng);
+            // the child wasn't evaluated yet, its mutation is scaled by the fitness of its parents
+            child.fitness = (a.fitness + b.fitness) / 2.0;
+            self.mutate(&mut child, rng)
+        } else {
+            self.generate_random_start(rng)
+        }
+    }
+
+    pub fn sexual_reproduction(&self, a: &Contestant, b: &Contestant, rng: &mut StdRng) -> Contestant {
         let mut layer_sizes: Vec<i32> = Vec::new();
-        for i in 0..self.max_layer_count {
-            if thread_rng().gen_range(0.0..1.0) < 0.5 {
-                layer_sizes.push(a.layer_sizes[i as usize]);
-            } else {
-                layer_sizes.push(b.layer_sizes[i as usize]);
-            }
+        // a layer keeps its activation and, if lamarckian, its weights
+        let mut activations = Vec::new();
+        let mut sources = Vec::new();
+        for i in 0..self.config.max_layer_count {
+            let source = if rng.gen_range(0.0..1.0) < 0.5 { a } else { b };
+            layer_sizes.push(source.layer_sizes[i as usize]);
+            activations.push(source.genome.activations[i as usize]);
+            sources.push(source);
+        }
+        let output_source = if rng.gen_range(0.0..1.0) < 0.5 { a } else { b };
+        sources.push(output_source);
+        let genome = Genome {
+            activations,
+            output_activation: output_source.genome.output_activation,
+            optimizer: if rng.gen_range(0.0..1.0) < 0.5 { a.genome.optimizer } else { b.genome.optimizer },
+            batch_size: (self.merge(a.genome.batch_size as f64, b.genome.batch_size as f64, rng).round() as usize).max(1),
+            l2: self.merge(a.genome.l2, b.genome.l2, rng),
+        };
+        let mut child = Contestant::new(self.merge(a.epochs as f64, b.epochs as f64, rng) as i32, self.merge(a.seed as f64, b.seed as f64, rng) as i32, layer_sizes, self.merge(a.learning_rate, b.learning_rate, rng), genome, &self.context);
+        if self.config.lamarckian {
+            child.inherit_weights(&sources);
         }
-        Contestant::new(EvolutionaryComputation::merge(a.epochs as f64, b.epochs as f64) as i32, EvolutionaryComputation::merge(a.seed as f64, b.seed as f64) as i32, layer_sizes, EvolutionaryComputation::merge(a.learning_rate, b.learning_rate))
+        child
     }
 
-    pub fn merge(a: f64, b: f64) -> f64 {
-        if thread_rng().gen_range(0.0..1.0) < SEXUAL_KEEP_RATE {
-            if thread_rng().gen_range(0.0..1.0) < 0.5 {
+    // one of the values or their average
+    pub fn merge(&self, a: f64, b: f64, rng: &mut StdRng) -> f64 {
+        if rng.gen_range(0.0..1.0) < self.config.sexual_keep_rate {
+            if rng.gen_range(0.0..1.0) < 0.5 {
                 a
             } else {
                 b
@@ -215,37 +535,100 @@ impl EvolutionaryComputation {
         }
     }
 
-    pub fn mutate(&self, contestant: &mut Contestant) -> Contestant {
+    pub fn mutate(&self, contestant: &mut Contestant, rng: &mut StdRng) -> Contestant {
         let mut layer_sizes: Vec<i32> = contestant.layer_sizes.clone();
-        for i in 0..self.max_layer_count as usize {
-            let r_number: f64 = thread_rng().gen_range(0.0..1.0);
+        for i in 0..self.config.max_layer_count as usize {
+            let r_number: f64 = rng.gen_range(0.0..1.0);
             if layer_sizes[i] > 0 {
-                if r_number < ASEXUAL_KEEP_RATE {
-                    layer_sizes[i] = self.calculate_change(1.0, self.max_nodes as f64, layer_sizes[i as usize] as f64, contestant.fitness) as i32;
+                if r_number < self.config.asexual_keep_rate {
+                    layer_sizes[i] = self.calculate_change(1.0, self.config.max_nodes as f64, layer_sizes[i as usize] as f64, contestant.fitness, rng) as i32;
                 } else {
                     layer_sizes[i] = 0;
                 }
-            } else if r_number < ASEXUAL_KEEP_RATE {
-                layer_sizes[i] = thread_rng().gen_range(0..self.max_nodes_start) as i32;
+            } else if r_number < self.config.asexual_keep_rate {
+                layer_sizes[i] = rng.gen_range(0..self.config.max_nodes_start) as i32;
             }
         }
-        unsafe {
-            let mut from_layer = Contestant::from_layer_sizes(layer_sizes.clone());
-            while Contestant::calculations_calculator(Contestant::add_io(from_layer.1.clone(), INPUT_SIZE, OUTPUT_SIZE)) > self.max_calculations {
-                let next_int = from_layer.0.len();
-                // println!("next_int: {}", next_int);
-                layer_sizes[from_layer.0[thread_rng().gen_range(0..next_int)] as usize] = 0;
-                from_layer = Contestant::from_layer_sizes(layer_sizes.clone());
+        let mut from_layer = Contestant::from_layer_sizes(layer_sizes.clone());
+        while Contestant::calculations_calculator(Contestant::add_io(from_layer.1.clone(), self.context.input_size, self.context.output_size)) > self.config.max_calculations {
+            let next_int = from_layer.0.len();
+            // println!("next_int: {}", next_int);
+            layer_sizes[from_layer.0[rng.gen_range(0..next_int)] as usize] = 0;
+            from_layer = Contestant::from_layer_sizes(layer_sizes.clone());
+        }
+
+        let mut child = Contestant::new(self.calculate_change(1.0, self.config.max_epochs as f64, contestant.epochs as f64, contestant.fitness, rng) as i32,
+                                        rng.gen_range(0..i32::MAX),
+                                        layer_sizes,
+                                        self.calculate_change(0.0, self.config.max_learning_rate, contestant.learning_rate, contestant.fitness, rng),
+                                        self.mutate_genome(&contestant.genome, rng),
+                                        &self.context);
+        if self.config.lamarckian {
+            child.inherit_weights(&vec![&*contestant; self.config.max_layer_count as usize + 1]);
+        }
+        child
+    }
+
+    pub fn mutate_genome(&self, genome: &Genome, rng: &mut StdRng) -> Genome {
+        let config = &self.config;
+        let mut genome = genome.clone();
+        for activation in genome.activations.iter_mut() {
+            if rng.gen_range(0.0..1.0) < config.gene_mutation_rate {
+                *activation = config.hidden_activations[rng.gen_range(0..config.hidden_activations.len())];
             }
         }
+        if rng.gen_range(0.0..1.0) < config.gene_mutation_rate {
+            genome.output_activation = config.output_activations[rng.gen_range(0..config.output_activations.len())];
+        }
+        genome.optimizer = if rng.gen_range(0.0..1.0) < config.gene_mutation_rate {
+            config.optimizers[rng.gen_range(0..config.optimizers.len())]
+        } else {
+            self.mutate_optimizer(genome.optimizer, rng)
+        };
+        genome.batch_size = self.mutate_gene(1.0, config.max_batch_size as f64, genome.batch_size as f64, rng).round() as usize;
+        genome.l2 = self.mutate_gene(0.0, config.max_l2, genome.l2, rng);
+        genome
+    }
 
-        Contestant::new(self.calculate_change(1.0, self.max_epochs as f64, contestant.epochs as f64, contestant.fitness) as i32,
-                        self.seed,
-                        layer_sizes,
-                        self.calculate_change(0.0, self.max_learning_rate, contestant.learning_rate, contestant.fitness))
+    // changes the hyperparameters, the type of the optimizer stays
+    pub fn mutate_optimizer(&self, optimizer: OptimizerConfig, rng: &mut StdRng) -> OptimizerConfig {
+        match optimizer {
+            OptimizerConfig::Sgd { momentum, nesterov } => OptimizerConfig::Sgd {
+                momentum: self.change_towards_one(MAX_MOMENTUM, momentum, rng),
+                nesterov,
+            },
+            OptimizerConfig::RmsProp { decay, epsilon } => OptimizerConfig::RmsProp {
+                decay: self.change_towards_one(MAX_DECAY, decay, rng),
+                epsilon,
+            },
+            OptimizerConfig::Adam { beta1, beta2, epsilon } => OptimizerConfig::Adam {
+                beta1: self.change_towards_one(MAX_BETA1, beta1, rng),
+                beta2: self.change_towards_one(MAX_BETA2, beta2, rng),
+                epsilon,
+            },
+            OptimizerConfig::AdamW { beta1, beta2, epsilon, weight_decay } => OptimizerConfig::AdamW {
+                beta1: self.change_towards_one(MAX_BETA1, beta1, rng),
+                beta2: self.change_towards_one(MAX_BETA2, beta2, rng),
+                epsilon,
+                weight_decay: self.mutate_gene(0.0, MAX_WEIGHT_DECAY, weight_decay, rng),
+            },
+        }
+    }
+
+    // for values in 0..=max close to 1 (momentum, decay rates), the logarithm of the distance to 1 gets changed
+    // instead of the value, so 0.999 and 0.9999 are as far apart as 0.9 and 0.99
+    fn change_towards_one(&self, max: f64, current: f64, rng: &mut StdRng) -> f64 {
+        1.0 - self.mutate_gene((1.0 - max).ln(), 0.0, (1.0 - current).ln(), rng).exp()
+    }
+
+    // unlike calculate_change the step doesn't depend on the fitness or the distance to min,
+    // so genes at their lower bound (batch size 1, no L2 or weight decay) can still grow
+    fn mutate_gene(&self, min: f64, max: f64, current: f64, rng: &mut StdRng) -> f64 {
+        let step = rng.gen_range(-1.0..1.0_f64).powi(GENE_STEP_EXP) * (max - min) * GENE_STEP;
+        (current + step).clamp(min, max)
     }
 
-    pub fn calculate_change(&self, min: f64, max: f64, current: f64, fitness: f64) -> f64 {
+    pub fn calculate_change(&self, min: f64, max: f64, current: f64, fitness: f64, rng: &mut StdRng) -> f64 {
         // java code:
         //     return Math.min(
         //                 Math.max(
@@ -262,7 +645,7 @@ impl EvolutionaryComputation {
             max.min(
                 current + (
                     (
-                        ((thread_rng().gen_range(0.0..1.0) * 2.0) - 1.0) as f64)
+                        ((rng.gen_range(0.0..1.0) * 2.0) - 1.0) as f64)
                         .powf(EXP_PARAMETER_CHANGE_RATE_EXP as f64)
                         * (current - min)
                         * EXP_PARAMETER_CHANGE_RATE_LINEAR as f64
@@ -272,3 +655,226 @@ impl EvolutionaryComputation {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::ec::fitness::{ErrorCost, WeightedCost};
+    use crate::ec::speciation::SpeciationConfig;
+    use crate::nn::activation::Activation;
+    use crate::nn::metrics::Metrics;
+
+    fn data_set() -> DataSet {
+        DataSet {
+            inputs: vec![vec![0.0, 1.0], vec![1.0, 0.0]],
+            targets: vec![vec![1.0, 0.0], vec![0.0, 1.0]],
+            class_names: Vec::new(),
+        }
+    }
+
+    fn evaluated(ec: &EvolutionaryComputation, layer_sizes: Vec<i32>, cost: f64) -> Contestant {
+        let genome = ec.random_genome(&mut stream_rng(0, 0));
+        let mut contestant = Contestant::new(10, 1, layer_sizes, 0.01, genome, &ec.context);
+        contestant.cost = cost;
+        contestant.evaluated = true;
+        contestant
+    }
+
+    // the genomes the run ends with, they depend on the costs of every generation before
+    fn run_with(seed: i32, threads: usize) -> Vec<(Vec<i32>, i32, f64, i32)> {
+        let config = EcConfig::builder().seed(seed).population_size(6).generations(3).min_epochs(1).max_epochs_start(3).max_epochs(5)
+            .max_nodes_start(5).max_nodes(10).max_learning_rate(0.1).threads(threads).build().unwrap();
+        let mut ec = EvolutionaryComputation::new(config, data_set(), data_set()).unwrap();
+        ec.run();
+        ec.population.iter().map(|c| (c.layer_sizes.clone(), c.epochs, c.learning_rate, c.seed)).collect()
+    }
+
+    #[test]
+    fn construction_errors_are_returned() {
+        let invalid = EcConfig { population_size: 0, ..EcConfig::default() };
+        assert!(matches!(EvolutionaryComputation::new(invalid, data_set(), data_set()), Err(ConfigError::Invalid(_))));
+        let empty = DataSet { inputs: Vec::new(), targets: Vec::new(), class_names: Vec::new() };
+        assert!(matches!(EvolutionaryComputation::new(EcConfig::default(), empty, data_set()), Err(ConfigError::DataSets(_))));
+        let wider = DataSet { inputs: vec![vec![0.0, 1.0, 2.0]], targets: vec![vec![1.0, 0.0]], class_names: Vec::new() };
+        assert!(matches!(EvolutionaryComputation::new(EcConfig::default(), data_set(), wider), Err(ConfigError::DataSets(_))));
+    }
+
+    #[test]
+    fn elites_are_copied_best_first() {
+        let config = EcConfig::builder().population_size(6).elitism(2).threads(1).build().unwrap();
+        let ec = EvolutionaryComputation::new(config, data_set(), data_set()).unwrap();
+        let mut population: Vec<Contestant> = [5.0, 3.0, 8.0, 1.0, 9.0, 4.0].iter().map(|cost| evaluated(&ec, vec![3, 0, 0, 0, 0], *cost)).collect();
+        let next_population = ec.next_gen(&mut population, 1);
+        assert_eq!(next_population.len(), 6);
+        assert_eq!((next_population[0].cost, next_population[1].cost), (1.0, 3.0));
+        assert!(next_population[0].evaluated && next_population[1].evaluated);
+    }
+
+    #[test]
+    fn runs_only_depend_on_the_seed() {
+        let single = run_with(42, 1);
+        assert_eq!(single, run_with(42, 4));
+        assert_eq!(single, run_with(42, 1));
+        assert_ne!(single, run_with(43, 4));
+    }
+
+    #[derive(Debug)]
+    struct AverageError;
+
+    impl FitnessFunction for AverageError {
+        fn cost(&self, contestant: &Contestant) -> f64 {
+            contestant.average_error
+        }
+
+        fn name(&self) -> String {
+            "AverageError".to_string()
+        }
+    }
+
+    #[test]
+    fn cache_hits_skip_the_evaluation() {
+        let config = EcConfig::builder().population_size(4).generations(1).min_epochs(1).max_epochs_start(3).max_epochs(3).threads(1).cache(true).build().unwrap();
+        let mut ec = EvolutionaryComputation::new(config, data_set(), data_set()).unwrap();
+        ec.set_fitness_function(Arc::new(AverageError));
+        // results no fit could produce
+        for k in 0..4 {
+            let contestant = ec.generate_random_start(&mut stream_rng(0, k));
+            let mut fake = contestant.clone();
+            fake.average_error = 100.0 + k as f64;
+            fake.metrics = Some(Metrics::from_outputs(&[vec![1.0, 0.0]], &[vec![1.0, 0.0]]));
+            ec.cache.insert(genome_hash(&fake, &ec.context), &fake);
+            ec.population.push(contestant);
+        }
+        let costs = Arc::new(Mutex::new(Vec::new()));
+        let observed = costs.clone();
+        ec.add_observer(Box::new(move |stats: &GenerationStats| observed.lock().unwrap().push((stats.cost.min, stats.cost.max))));
+        let result = ec.run();
+        assert_eq!(*costs.lock().unwrap(), vec![(100.0, 103.0)]);
+        assert_eq!(ec.cache.len(), 4);
+        // the hall of fame needs trained networks, so cached contestants are fitted before they get in
+        let hall_of_fame = result.hall_of_fame.contestants();
+        assert_eq!(hall_of_fame.iter().map(|c| c.cost).collect::<Vec<f64>>(), vec![100.0, 101.0, 102.0, 103.0]);
+        for (k, contestant) in [0, 1, 2, 3].iter().zip(hall_of_fame) {
+            let untrained = ec.generate_random_start(&mut stream_rng(0, *k));
+            assert!(!contestant.cached && contestant.metrics.is_some());
+            assert_ne!(contestant.neural_network.weights[0].matrix, untrained.neural_network.weights[0].matrix);
+        }
+    }
+
+    #[test]
+    fn elites_are_the_best_contestants_with_speciation() {
+        let config = EcConfig::builder().population_size(6).elitism(1).threads(1).speciation(SpeciationConfig::default()).build().unwrap();
+        let ec = EvolutionaryComputation::new(config, data_set(), data_set()).unwrap();
+        // the best contestant shares its species with four others, the second best one is alone
+        let mut population: Vec<Contestant> = (0..5).map(|i| evaluated(&ec, vec![50, 0, 0, 0, 0], 10.0 + i as f64)).collect();
+        population.push(evaluated(&ec, vec![0, 0, 50, 50, 50], 11.0));
+        let species = ec.config().speciation.as_ref().unwrap().species(&population, ec.config());
+        assert_eq!(species, vec![0, 0, 0, 0, 0, 1]);
+
+        let next_population = ec.next_gen(&mut population, 1);
+        assert_eq!(next_population[0].cost, 10.0);
+        assert_eq!(next_population[0].layer_sizes, vec![50, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn resumed_runs_continue_like_uninterrupted_ones() {
+        let path = std::env::temp_dir().join(format!("nn_rust_resume_{}.checkpoint", std::process::id()));
+        let path = path.to_str().unwrap();
+        let fitness_function: Arc<dyn FitnessFunction> = Arc::new(WeightedCost { error_weight: 1.0, calculations_weight: 0.01, epochs_weight: 0.1 });
+        let config = EcConfig::builder().population_size(6).generations(4).min_epochs(1).max_epochs_start(3).max_epochs(5)
+            .max_nodes_start(5).max_nodes(10).max_learning_rate(0.1).elitism(1).threads(2)
+            .fitness_function(fitness_function.clone()).checkpoint(2, path).build().unwrap();
+
+        let mut straight = EvolutionaryComputation::new(config.clone(), data_set(), data_set()).unwrap();
+        straight.run();
+
+        let mut interrupted = EvolutionaryComputation::new(config, data_set(), data_set()).unwrap();
+        interrupted.start();
+        for _ in 0..2 {
+            interrupted.evaluate();
+            interrupted.advance();
+        }
+        assert!(matches!(EvolutionaryComputation::resume(path, data_set(), data_set(), Arc::new(ErrorCost)), Err(CheckpointError::Invalid(_))));
+        // same sizes, other values
+        let mut other_data = data_set();
+        other_data.inputs[0][0] = 0.5;
+        match EvolutionaryComputation::resume(path, other_data.clone(), data_set(), fitness_function.clone()) {
+            Err(CheckpointError::Invalid(message)) => assert_eq!(message, "the data sets are not the ones the run was started with"),
+            other => panic!("expected an invalid checkpoint, got {:?}", other.err()),
+        }
+        assert!(EvolutionaryComputation::resume(path, data_set(), other_data, fitness_function.clone()).is_err());
+        let mut resumed = EvolutionaryComputation::resume(path, data_set(), data_set(), fitness_function).unwrap();
+        resumed.run();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(resumed.current_generation(), 4);
+        assert_eq!(resumed.checkpoint().to_bytes().unwrap(), straight.checkpoint().to_bytes().unwrap());
+    }
+
+    #[test]
+    fn nsga2_runs_return_their_pareto_front() {
+        let config = |search| EcConfig::builder().population_size(6).generations(2).min_epochs(1).max_epochs_start(3).max_epochs(5)
+            .max_nodes_start(5).max_nodes(10).max_learning_rate(0.1).threads(1).search(search).build().unwrap();
+        let mut ec = EvolutionaryComputation::new(config(SearchMode::Nsga2), data_set(), data_set()).unwrap();
+        let front = ec.run().pareto_front;
+        assert!(!front.is_empty());
+        assert_eq!(front.len(), ec.pareto_front().len());
+        for a in &front {
+            assert!(a.evaluated && !a.cached && a.rank == 0);
+            assert!(front.iter().all(|b| !nsga2::dominates(&b.objectives(), &a.objectives())));
+        }
+        let mut ec = EvolutionaryComputation::new(config(SearchMode::SingleObjective), data_set(), data_set()).unwrap();
+        assert!(ec.run().pareto_front.is_empty());
+    }
+
+    #[test]
+    fn genes_stay_in_range_and_leave_their_bounds() {
+        let config = EcConfig::builder().gene_mutation_rate(0.0).max_batch_size(8).max_l2(0.01).threads(1).build().unwrap();
+        let ec = EvolutionaryComputation::new(config, data_set(), data_set()).unwrap();
+        // every gene starts at one of its bounds
+        let genome = Genome {
+            activations: vec![Activation::Sigmoid; 5],
+            output_activation: Activation::Sigmoid,
+            optimizer: OptimizerConfig::AdamW { beta1: MAX_BETA1, beta2: 0.0, epsilon: 1e-8, weight_decay: 0.0 },
+            batch_size: 1,
+            l2: 0.0,
+        };
+        let mutants: Vec<Genome> = (0..200).map(|k| ec.mutate_genome(&genome, &mut stream_rng(1, k))).collect();
+        let genes = |mutant: &Genome| match mutant.optimizer {
+            OptimizerConfig::AdamW { beta1, beta2, weight_decay, .. } => (beta1, beta2, weight_decay),
+            other => panic!("the optimizer type changed to {:?}", other),
+        };
+        for mutant in &mutants {
+            let (beta1, beta2, weight_decay) = genes(mutant);
+            assert!((1..=8).contains(&mutant.batch_size));
+            assert!((0.0..=0.01).contains(&mutant.l2));
+            assert!((0.0..=MAX_BETA1).contains(&beta1) && (0.0..=MAX_BETA2).contains(&beta2));
+            assert!((0.0..=MAX_WEIGHT_DECAY).contains(&weight_decay));
+        }
+        assert!(mutants.iter().any(|m| m.batch_size > 1));
+        assert!(mutants.iter().any(|m| m.l2 > 0.0));
+        assert!(mutants.iter().any(|m| genes(m).0 < MAX_BETA1));
+        assert!(mutants.iter().any(|m| genes(m).1 > 0.0));
+        assert!(mutants.iter().any(|m| genes(m).2 > 0.0));
+    }
+
+    #[test]
+    fn crossover_children_are_mutated_with_the_fitness_of_their_parents() {
+        let config = EcConfig::builder().min_epochs(1).max_epochs_start(50).max_epochs(100).max_learning_rate(0.1)
+            .asexual_reproduction(0.0).sexual_reproduction(1.0).threads(1).build().unwrap();
+        let ec = EvolutionaryComputation::new(config, data_set(), data_set()).unwrap();
+        let parent = |epochs, learning_rate| {
+            let mut parent = evaluated(&ec, vec![8, 0, 0, 0, 0], 1.0);
+            parent.epochs = epochs;
+            parent.learning_rate = learning_rate;
+            parent.fitness = 1.0;
+            parent
+        };
+        let (a, b) = (parent(40, 0.02), parent(60, 0.04));
+        let children: Vec<Contestant> = (0..50).map(|k| ec.breed(&a, &b, &mut stream_rng(2, k))).collect();
+        // without a fitness the steps were infinite and every child ended up at a bound
+        assert!(children.iter().filter(|c| c.epochs > 1 && c.epochs < 100).count() > children.len() / 2);
+        assert!(children.iter().filter(|c| c.learning_rate > 0.0 && c.learning_rate < 0.1).count() > children.len() / 2);
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt::Debug;
+use crate::ec::contestant::Contestant;
+
+// turns an evaluated contestant into its cost, costs have to be positive and lower is better,
+// the fitness used for the selection is derived from it
+pub trait FitnessFunction: Debug + Send + Sync {
+    fn cost(&self, contestant: &Contestant) -> f64;
+
+    // identifies the function and its parameters in checkpoints, so it has to stay the same across versions
+    fn name(&self) -> String;
+}
+
+// wrongly classified test samples, averaged over the classes, plus the ones of the worst class
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErrorCost;
+
+impl FitnessFunction for ErrorCost {
+    fn cost(&self, contestant: &Contestant) -> f64 {
+        let confusion = match &contestant.metrics {
+            Some(metrics) => &metrics.confusion,
+            None => return f64::INFINITY,
+        };
+        let errors: Vec<f64> = (0..confusion.classes()).map(|c| confusion.errors(c) as f64).collect();
+        let average_error = errors.iter().sum::<f64>() / errors.len() as f64;
+        let max_error = errors.iter().copied().fold(0.0, f64::max);
+        average_error + max_error
+    }
+
+    fn name(&self) -> String {
+        "ErrorCost".to_string()
+    }
+}
+
+// error in percent of the test samples plus penalties on the size and the training time
+#[derive(Debug, Clone, Copy)]
+pub struct WeightedCost {
+    pub error_weight: f64,
+    pub calculations_weight: f64,
+    pub epochs_weight: f64,
+}
+
+impl FitnessFunction for WeightedCost {
+    fn cost(&self, contestant: &Contestant) -> f64 {
+        self.error_weight * (100.0 - contestant.accuracy)
+            + self.calculations_weight * contestant.calculations as f64
+            + self.epochs_weight * contestant.epochs as f64
+    }
+
+    fn name(&self) -> String {
+        format!("WeightedCost(error_weight: {}, calculations_weight: {}, epochs_weight: {})", self.error_weight, self.calculations_weight, self.epochs_weight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ec::evaluation_context::EvaluationContext;
+    use crate::ec::genome::Genome;
+    use crate::nn::activation::Activation;
+    use crate::nn::data_set::DataSet;
+    use crate::nn::metrics::{argmax, Metrics};
+    use crate::nn::optimizer::OptimizerConfig;
+
+    fn one_hot(class: usize) -> Vec<f64> {
+        (0..3).map(|i| if i == class { 1.0 } else { 0.0 }).collect()
+    }
+
+    // class 0: 1 of 3 wrong, class 1: 2 of 2 wrong, class 2: all right
+    fn outputs_and_targets() -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+        let targets: Vec<Vec<f64>> = [0, 0, 0, 1, 1, 2].iter().map(|c| one_hot(*c)).collect();
+        let outputs: Vec<Vec<f64>> = [0, 0, 2, 0, 2, 2].iter().map(|c| one_hot(*c)).collect();
+        (outputs, targets)
+    }
+
+    // the cost Contestant::fit used to compute itself: wrong samples per class, averaged, plus the most of one class
+    fn baseline_cost(outputs: &[Vec<f64>], targets: &[Vec<f64>]) -> f64 {
+        let mut errors_per_class = vec![0.0; targets[0].len()];
+        for (output, target) in outputs.iter().zip(targets) {
+            if argmax(output) != argmax(target) {
+                errors_per_class[argmax(target)] += 1.0;
+            }
+        }
+        let max_error = errors_per_class.iter().copied().fold(0.0, f64::max);
+        errors_per_class.iter().sum::<f64>() / errors_per_class.len() as f64 + max_error
+    }
+
+    fn genome() -> Genome {
+        Genome { activations: vec![Activation::Sigmoid; 2], output_activation: Activation::Sigmoid, optimizer: OptimizerConfig::default(), batch_size: 1, l2: 0.0 }
+    }
+
+    fn contestant() -> Contestant {
+        let data_set = DataSet::new(vec![vec![0.0, 1.0]], vec![one_hot(0)]);
+        let context = EvaluationContext::new(data_set.clone(), data_set, 2, 3);
+        let mut contestant = Contestant::new(20, 1, vec![7, 0], 0.01, genome(), &context);
+        let (outputs, targets) = outputs_and_targets();
+        let metrics = Metrics::from_outputs(&outputs, &targets);
+        contestant.accuracy = metrics.accuracy * 100.0;
+        contestant.metrics = Some(metrics);
+        contestant
+    }
+
+    #[test]
+    fn error_cost_matches_the_baseline_cost() {
+        let (outputs, targets) = outputs_and_targets();
+        assert_eq!(ErrorCost.cost(&contestant()), baseline_cost(&outputs, &targets));
+        assert_eq!(ErrorCost.cost(&contestant()), 1.0 + 2.0);
+        let mut unevaluated = contestant();
+        unevaluated.metrics = None;
+        assert_eq!(ErrorCost.cost(&unevaluated), f64::INFINITY);
+    }
+
+    #[test]
+    fn weighted_cost_adds_up_the_penalties() {
+        let contestant = contestant();
+        let weighted = WeightedCost { error_weight: 2.0, calculations_weight: 0.5, epochs_weight: 0.1 };
+        let expected = 2.0 * 50.0 + 0.5 * contestant.calculations as f64 + 0.1 * 20.0;
+        assert!((weighted.cost(&contestant) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn names_include_the_parameters() {
+        assert_eq!(ErrorCost.name(), "ErrorCost");
+        let weighted = WeightedCost { error_weight: 2.0, calculations_weight: 0.5, epochs_weight: 0.1 };
+        assert_eq!(weighted.name(), "WeightedCost(error_weight: 2, calculations_weight: 0.5, epochs_weight: 0.1)");
+        assert_ne!(WeightedCost { epochs_weight: 0.2, ..weighted }.name(), weighted.name());
+    }
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use crate::nn::activation::Activation;
+use crate::nn::optimizer::OptimizerConfig;
+
+// the training configuration a contestant evolves next to its layer sizes, epochs and learning rate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Genome {
+    // one per slot of the layer sizes, empty slots keep theirs for when they get filled again
+    pub activations: Vec<Activation>,
+    pub output_activation: Activation,
+    pub optimizer: OptimizerConfig,
+    pub batch_size: usize,
+    // strength of the L2 penalty on the weights
+    pub l2: f64,
+}
+
+impl Genome {
+    // the activations of a network built from the slots in `lookup_table`, followed by the output activation
+    pub fn layer_activations(&self, lookup_table: &[i32]) -> Vec<Activation> {
+        let mut activations: Vec<Activation> = lookup_table.iter().map(|slot| self.activations[*slot as usize]).collect();
+        activations.push(self.output_activation);
+        activations
+    }
+}
@@ -0,0 +1,202 @@
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::ec::contestant::Contestant;
+use crate::ec::genome::Genome;
+use crate::nn::metrics::Metrics;
+use crate::nn::model::{ModelError, ModelFormat};
+
+// the best contestants of a whole run with their trained networks, lowest cost first,
+// every genome (see genome_hash) is in it only once
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HallOfFame {
+    size: usize,
+    keys: Vec<u64>,
+    contestants: Vec<Contestant>,
+}
+
+// what produced a saved network, written next to its model file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContestantSummary {
+    pub rank: usize,
+    pub cost: f64,
+    pub accuracy: f64,
+    pub average_error: f64,
+    pub max_error: f64,
+    pub layers: Vec<i32>,
+    pub layer_sizes: Vec<i32>,
+    pub calculations: i32,
+    pub epochs: i32,
+    pub learning_rate: f64,
+    pub seed: i32,
+    pub genome: Genome,
+    pub metrics: Option<Metrics>,
+}
+
+impl ContestantSummary {
+    pub fn new(rank: usize, contestant: &Contestant) -> ContestantSummary {
+        ContestantSummary {
+            rank,
+            cost: contestant.cost,
+            accuracy: contestant.accuracy,
+            average_error: contestant.average_error,
+            max_error: contestant.max_error,
+            layers: contestant.layers.clone(),
+            layer_sizes: contestant.layer_sizes.clone(),
+            calculations: contestant.calculations,
+            epochs: contestant.epochs,
+            learning_rate: contestant.learning_rate,
+            seed: contestant.seed,
+            genome: contestant.genome.clone(),
+            metrics: contestant.metrics.clone(),
+        }
+    }
+}
+
+impl HallOfFame {
+    pub fn new(size: usize) -> HallOfFame {
+        HallOfFame { size, keys: Vec::new(), contestants: Vec::new() }
+    }
+
+    pub fn contestants(&self) -> &[Contestant] {
+        &self.contestants
+    }
+
+    pub fn best(&self) -> Option<&Contestant> {
+        self.contestants.first()
+    }
+
+    pub fn len(&self) -> usize {
+        self.contestants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contestants.is_empty()
+    }
+
+    // whether a contestant with this key and cost would get in
+    pub fn accepts(&self, key: u64, cost: f64) -> bool {
+        if self.size == 0 || self.keys.contains(&key) {
+            return false;
+        }
+        self.contestants.len() < self.size || cost < self.contestants[self.contestants.len() - 1].cost
+    }
+
+    // the network of the contestant has to be trained
+    pub fn insert(&mut self, key: u64, contestant: Contestant) {
+        if !self.accepts(key, contestant.cost) {
+            return;
+        }
+        let position = self.contestants.partition_point(|c| c.cost <= contestant.cost);
+        self.keys.insert(position, key);
+        self.contestants.insert(position, contestant);
+        self.keys.truncate(self.size);
+        self.contestants.truncate(self.size);
+    }
+
+    // both have to be from runs on the same data sets
+    pub fn merge(&mut self, other: &HallOfFame) {
+        for (key, contestant) in other.keys.iter().zip(&other.contestants) {
+            self.insert(*key, contestant.clone());
+        }
+    }
+
+    // writes contestant_<rank>.model(.json) with the network and contestant_<rank>.json with its summary
+    // into `directory`, the best one has rank 1
+    pub fn save<P: AsRef<Path>>(&self, directory: P, format: ModelFormat) -> Result<(), ModelError> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory)?;
+        for (i, contestant) in self.contestants.iter().enumerate() {
+            let rank = i + 1;
+            let model_file = match format {
+                ModelFormat::Json => format!("contestant_{}.model.json", rank),
+                ModelFormat::Binary => format!("contestant_{}.model", rank),
+            };
+            contestant.neural_network.save(directory.join(model_file), format)?;
+            let summary = serde_json::to_string_pretty(&ContestantSummary::new(rank, contestant))?;
+            fs::write(directory.join(format!("contestant_{}.json", rank)), summary)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ec::evaluation_context::EvaluationContext;
+    use crate::nn::activation::Activation;
+    use crate::nn::data_set::DataSet;
+    use crate::nn::optimizer::OptimizerConfig;
+
+    fn context() -> EvaluationContext {
+        let data_set = DataSet::new(vec![vec![0.0, 1.0]], vec![vec![1.0, 0.0]]);
+        EvaluationContext::new(data_set.clone(), data_set, 2, 2)
+    }
+
+    fn contestant(cost: f64, context: &EvaluationContext) -> Contestant {
+        let genome = Genome { activations: vec![Activation::Sigmoid; 1], output_activation: Activation::Sigmoid, optimizer: OptimizerConfig::default(), batch_size: 1, l2: 0.0 };
+        let mut contestant = Contestant::new(1, 1, vec![7], 0.1, genome, context);
+        contestant.cost = cost;
+        contestant
+    }
+
+    fn costs(hall_of_fame: &HallOfFame) -> Vec<f64> {
+        hall_of_fame.contestants().iter().map(|c| c.cost).collect()
+    }
+
+    #[test]
+    fn keeps_the_best_lowest_cost_first() {
+        let context = context();
+        let mut hall_of_fame = HallOfFame::new(3);
+        assert!(hall_of_fame.is_empty() && hall_of_fame.best().is_none());
+        for (key, cost) in [5.0, 3.0, 8.0, 1.0, 9.0, 4.0].iter().enumerate() {
+            hall_of_fame.insert(key as u64, contestant(*cost, &context));
+        }
+        assert_eq!(costs(&hall_of_fame), vec![1.0, 3.0, 4.0]);
+        assert_eq!(hall_of_fame.len(), 3);
+        assert_eq!(hall_of_fame.best().unwrap().cost, 1.0);
+        assert!(!hall_of_fame.accepts(10, 4.0));
+        assert!(hall_of_fame.accepts(10, 3.5));
+        // equal costs keep the one that got in first
+        hall_of_fame.insert(10, contestant(3.0, &context));
+        assert_eq!(costs(&hall_of_fame), vec![1.0, 3.0, 3.0]);
+        assert_eq!(hall_of_fame.keys, vec![3, 1, 10]);
+    }
+
+    #[test]
+    fn genomes_are_only_in_it_once() {
+        let context = context();
+        let mut hall_of_fame = HallOfFame::new(3);
+        hall_of_fame.insert(1, contestant(2.0, &context));
+        assert!(!hall_of_fame.accepts(1, 1.0));
+        hall_of_fame.insert(1, contestant(1.0, &context));
+        assert_eq!(costs(&hall_of_fame), vec![2.0]);
+    }
+
+    #[test]
+    fn size_zero_takes_nothing() {
+        let mut hall_of_fame = HallOfFame::new(0);
+        assert!(!hall_of_fame.accepts(1, 0.0));
+        hall_of_fame.insert(1, contestant(0.0, &context()));
+        assert!(hall_of_fame.is_empty());
+    }
+
+    #[test]
+    fn save_writes_models_and_summaries() {
+        let dir = std::env::temp_dir().join(format!("nn_rust_hall_of_fame_{}", std::process::id()));
+        let context = context();
+        let mut hall_of_fame = HallOfFame::new(2);
+        hall_of_fame.insert(1, contestant(2.0, &context));
+        hall_of_fame.insert(2, contestant(1.0, &context));
+        hall_of_fame.save(&dir, ModelFormat::Binary).unwrap();
+        for rank in 1..=2 {
+            let nn = crate::nn::neural_network::NeuralNetwork::load(dir.join(format!("contestant_{}.model", rank))).unwrap();
+            assert_eq!(nn.weights[0].matrix, hall_of_fame.contestants()[rank - 1].neural_network.weights[0].matrix);
+            let summary: serde_json::Value = serde_json::from_slice(&fs::read(dir.join(format!("contestant_{}.json", rank))).unwrap()).unwrap();
+            assert_eq!(summary["rank"], rank);
+            assert_eq!(summary["cost"], rank as f64);
+        }
+        assert!(!dir.join("contestant_3.json").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,313 @@
+use std::path::Path;
+use std::sync::Arc;
+use rayon::ThreadPoolBuilder;
+use serde::{Deserialize, Serialize};
+use crate::ec::config::{ConfigError, EcConfig};
+use crate::ec::contestant::Contestant;
+use crate::ec::evaluation_context::EvaluationContext;
+use crate::ec::evolutionary_computation::{EvolutionaryComputation, RunResult};
+use crate::ec::hall_of_fame::HallOfFame;
+use crate::ec::random::derive_seed;
+use crate::nn::data_set::DataSet;
+
+// which islands send their migrants where
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Topology {
+    // island k sends to island k + 1, the last one to the first one
+    #[default]
+    Ring,
+    // every island sends to all others
+    FullyConnected,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MigrationConfig {
+    // generations between two migrations, 0 disables them
+    pub interval: i32,
+    // the best contestants an island sends to each of its neighbours
+    pub migrants: usize,
+    pub topology: Topology,
+}
+
+impl Default for MigrationConfig {
+    fn default() -> MigrationConfig {
+        MigrationConfig {
+            interval: 5,
+            migrants: 2,
+            topology: Topology::default(),
+        }
+    }
+}
+
+impl MigrationConfig {
+    // islands an island gets its immigrants from
+    pub fn sources(&self, island: usize, islands: usize) -> Vec<usize> {
+        match self.topology {
+            Topology::Ring => vec![(island + islands - 1) % islands],
+            Topology::FullyConnected => (0..islands).collect(),
+        }.into_iter().filter(|source| *source != island).collect()
+    }
+
+    pub fn validate(&self, islands: &[EcConfig]) -> Result<(), ConfigError> {
+        let invalid = |message: String| Err(ConfigError::Invalid(message));
+        if islands.is_empty() {
+            return invalid("there has to be at least one island".to_string());
+        }
+        if self.interval < 0 {
+            return invalid(format!("the migration interval must not be negative, got {}", self.interval));
+        }
+        // the islands evaluate their generations in lockstep
+        if islands.iter().any(|config| config.generations != islands[0].generations) {
+            return invalid("all islands need the same number of generations".to_string());
+        }
+        // immigrants keep their layer sizes and activations, one per slot
+        if islands.iter().any(|config| config.max_layer_count != islands[0].max_layer_count) {
+            return invalid("all islands need the same max_layer_count".to_string());
+        }
+        for (k, config) in islands.iter().enumerate() {
+            let immigrants = self.migrants * self.sources(k, islands.len()).len();
+            if self.interval > 0 && immigrants >= config.population_size as usize {
+                return invalid(format!("island {} gets {} immigrants, more than its population of {} can take", k, immigrants, config.population_size));
+            }
+        }
+        Ok(())
+    }
+}
+
+// several populations that evolve on their own and exchange their best contestants every
+// `interval` generations, which keeps a single population from converging too early
+pub struct IslandModel {
+    islands: Vec<EvolutionaryComputation>,
+    migration: MigrationConfig,
+}
+
+impl IslandModel {
+    // one island per config, they share the data sets
+    pub fn new(configs: Vec<EcConfig>, migration: MigrationConfig, training_set: DataSet, test_set: DataSet) -> Result<IslandModel, ConfigError> {
+        migration.validate(&configs)?;
+        let context = Arc::new(EvaluationContext::from_data_sets(training_set, test_set).map_err(ConfigError::DataSets)?);
+        // the islands are evaluated one after the other, so one pool is enough, it gets the most threads any island asks for
+        let threads = if configs.iter().any(|config| config.threads == 0) { 0 } else { configs.iter().map(|config| config.threads).max().unwrap_or(0) };
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(threads).build()?);
+        let islands = configs.into_iter().map(|config| EvolutionaryComputation::with_pool(config, context.clone(), pool.clone())).collect::<Result<_, _>>()?;
+        Ok(IslandModel { islands, migration })
+    }
+
+    // `islands` copies of the config with seeds derived from its seed, the files
+    // of island k get .island<k> in front of their extension
+    pub fn uniform(config: EcConfig, islands: usize, migration: MigrationConfig, training_set: DataSet, test_set: DataSet) -> Result<IslandModel, ConfigError> {
+        let configs = (0..islands).map(|k| {
+            let mut island = config.clone();
+            island.seed = derive_seed(config.seed as u64, k as u64) as i32;
+            for path in [&mut island.checkpoint_path, &mut island.stats_path, &mut island.cache_path, &mut island.hall_of_fame_path].into_iter().flatten() {
+                *path = island_path(path, k);
+            }
+            island
+        }).collect();
+        IslandModel::new(configs, migration, training_set, test_set)
+    }
+
+    pub fn islands(&self) -> &[EvolutionaryComputation] {
+        &self.islands
+    }
+
+    // to add observers to a single island
+    pub fn island_mut(&mut self, island: usize) -> &mut EvolutionaryComputation {
+        &mut self.islands[island]
+    }
+
+    // the hall of fame and the pareto front of the result are the merged ones of all islands
+    pub fn run(&mut self) -> RunResult {
+        for island in self.islands.iter_mut() {
+            island.start();
+        }
+        while !self.islands[0].is_finished() {
+            let generation = self.islands[0].current_generation();
+            for (k, island) in self.islands.iter_mut().enumerate() {
+                println!("Island {}", k);
+                island.evaluate();
+            }
+            if self.migration.interval > 0 && (generation + 1) % self.migration.interval == 0 {
+                self.migrate();
+            }
+            for (k, island) in self.islands.iter_mut().enumerate() {
+                println!("Island {}", k);
+                island.advance();
+            }
+        }
+        let mut hall_of_fame = HallOfFame::new(self.islands.iter().map(|island| island.config().hall_of_fame_size).max().unwrap_or(0));
+        let mut fronts = Vec::new();
+        for island in self.islands.iter_mut() {
+            let result = island.finish();
+            hall_of_fame.merge(&result.hall_of_fame);
+            fronts.extend(result.pareto_front);
+        }
+        RunResult {
+            generations: self.islands[0].current_generation(),
+            hall_of_fame,
+            // what one island found can be dominated by another one
+            pareto_front: EvolutionaryComputation::non_dominated(&fronts),
+        }
+    }
+
+    // all emigrants are chosen before any island takes its immigrants
+    fn migrate(&mut self) {
+        let emigrants: Vec<Vec<Contestant>> = self.islands.iter().map(|island| island.emigrants(self.migration.migrants)).collect();
+        let islands = self.islands.len();
+        for (k, island) in self.islands.iter_mut().enumerate() {
+            let immigrants: Vec<Contestant> = self.migration.sources(k, islands).iter().flat_map(|source| emigrants[*source].iter().cloned()).collect();
+            println!("Island {} takes {} immigrants", k, immigrants.len());
+            island.immigrate(immigrants);
+        }
+    }
+}
+
+fn island_path(path: &str, island: usize) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.island{}.{}", stem, island, extension.to_string_lossy()),
+        None => format!("{}.island{}", stem, island),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_set() -> DataSet {
+        DataSet {
+            inputs: vec![vec![0.0, 1.0], vec![1.0, 0.0]],
+            targets: vec![vec![1.0, 0.0], vec![0.0, 1.0]],
+            class_names: Vec::new(),
+        }
+    }
+
+    fn config() -> EcConfig {
+        EcConfig::builder().population_size(4).generations(2).min_epochs(1).max_epochs_start(2).max_epochs(2)
+            .max_nodes_start(5).max_nodes(10).max_learning_rate(0.1).threads(1).build().unwrap()
+    }
+
+    fn migration(interval: i32, migrants: usize, topology: Topology) -> MigrationConfig {
+        MigrationConfig { interval, migrants, topology }
+    }
+
+    // what identifies a contestant across islands
+    fn genomes(contestants: &[Contestant]) -> Vec<(Vec<i32>, i32, f64, i32)> {
+        contestants.iter().map(|c| (c.layer_sizes.clone(), c.epochs, c.learning_rate, c.seed)).collect()
+    }
+
+    fn invalid_message(migration: &MigrationConfig, islands: &[EcConfig]) -> String {
+        match migration.validate(islands) {
+            Err(ConfigError::Invalid(message)) => message,
+            other => panic!("expected an invalid config, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sources_of_the_topologies() {
+        let ring = migration(1, 1, Topology::Ring);
+        assert_eq!(ring.sources(0, 3), vec![2]);
+        assert_eq!(ring.sources(2, 3), vec![1]);
+        assert!(ring.sources(0, 1).is_empty());
+        let fully_connected = migration(1, 1, Topology::FullyConnected);
+        assert_eq!(fully_connected.sources(1, 3), vec![0, 2]);
+        assert!(fully_connected.sources(0, 1).is_empty());
+    }
+
+    #[test]
+    fn validate_checks_the_islands() {
+        let islands = vec![config(), config()];
+        assert!(migration(1, 3, Topology::Ring).validate(&islands).is_ok());
+        // without migrations nobody has to make room
+        assert!(migration(0, 4, Topology::Ring).validate(&islands).is_ok());
+        assert_eq!(invalid_message(&migration(1, 1, Topology::Ring), &[]), "there has to be at least one island");
+        assert_eq!(invalid_message(&migration(-1, 1, Topology::Ring), &islands), "the migration interval must not be negative, got -1");
+        assert_eq!(invalid_message(&migration(1, 4, Topology::Ring), &islands), "island 0 gets 4 immigrants, more than its population of 4 can take");
+        assert_eq!(invalid_message(&migration(1, 2, Topology::FullyConnected), &[config(), config(), config()]), "island 0 gets 4 immigrants, more than its population of 4 can take");
+        let mut longer = config();
+        longer.generations = 3;
+        assert_eq!(invalid_message(&migration(1, 1, Topology::Ring), &[config(), longer]), "all islands need the same number of generations");
+        let mut deeper = config();
+        deeper.max_layer_count += 1;
+        assert_eq!(invalid_message(&migration(1, 1, Topology::Ring), &[config(), deeper.clone()]), "all islands need the same max_layer_count");
+        // like the generations, independent of the interval
+        assert!(migration(0, 1, Topology::Ring).validate(&[config(), deeper]).is_err());
+    }
+
+    #[test]
+    fn invalid_islands_are_an_error() {
+        assert!(matches!(IslandModel::uniform(config(), 2, migration(1, 4, Topology::Ring), data_set(), data_set()), Err(ConfigError::Invalid(_))));
+        let invalid = EcConfig { population_size: 0, ..config() };
+        assert!(matches!(IslandModel::new(vec![config(), invalid], migration(0, 1, Topology::Ring), data_set(), data_set()), Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn island_paths() {
+        assert_eq!(island_path("runs/stats.csv", 2), Path::new("runs").join("stats.island2.csv").to_string_lossy());
+        assert_eq!(island_path("cache", 0), "cache.island0");
+    }
+
+    #[test]
+    fn uniform_islands_get_their_own_seeds_and_files() {
+        let mut config = config();
+        config.stats_path = Some("stats.jsonl".to_string());
+        let model = IslandModel::uniform(config.clone(), 3, migration(1, 1, Topology::Ring), data_set(), data_set()).unwrap();
+        assert_eq!(model.islands().len(), 3);
+        for (k, island) in model.islands().iter().enumerate() {
+            assert_eq!(island.config().seed, derive_seed(config.seed as u64, k as u64) as i32);
+            assert_eq!(island.config().stats_path, Some(format!("stats.island{}.jsonl", k)));
+            assert!(Arc::ptr_eq(&island.context(), &model.islands()[0].context()));
+        }
+    }
+
+    #[test]
+    fn migration_replaces_the_worst_with_the_best_of_the_neighbours() {
+        let mut model = IslandModel::uniform(config(), 2, migration(1, 2, Topology::Ring), data_set(), data_set()).unwrap();
+        for island in model.islands.iter_mut() {
+            island.start();
+            island.evaluate();
+        }
+        let best: Vec<Vec<Contestant>> = model.islands().iter().map(|island| island.emigrants(2)).collect();
+        let before: Vec<Vec<Contestant>> = model.islands().iter().map(|island| island.emigrants(4)).collect();
+        model.migrate();
+        for k in 0..2 {
+            let after = model.islands()[k].emigrants(4);
+            assert_eq!(after.len(), 4);
+            let after = genomes(&after);
+            for genome in genomes(&best[1 - k]) {
+                assert!(after.contains(&genome), "island {} is missing {:?}", k, genome);
+            }
+            // costs can be equal, so only the costs of the replaced ones are known
+            let (kept, replaced): (Vec<&Contestant>, Vec<&Contestant>) = before[k].iter().partition(|c| after.contains(&genomes(&[(*c).clone()])[0]));
+            assert_eq!((kept.len(), replaced.len()), (2, 2));
+            assert!(kept.iter().all(|a| replaced.iter().all(|b| a.cost <= b.cost)));
+        }
+    }
+
+    #[test]
+    fn runs_are_deterministic() {
+        let run = |topology| {
+            let mut model = IslandModel::uniform(config(), 2, migration(1, 1, topology), data_set(), data_set()).unwrap();
+            let result = model.run();
+            assert_eq!(result.generations, 2);
+            genomes(result.hall_of_fame.contestants())
+        };
+        let ring = run(Topology::Ring);
+        assert!(!ring.is_empty());
+        assert_eq!(ring, run(Topology::Ring));
+        assert_eq!(run(Topology::FullyConnected), run(Topology::FullyConnected));
+    }
+
+    #[test]
+    fn pareto_fronts_are_merged() {
+        let config = EcConfig { search: crate::ec::config::SearchMode::Nsga2, ..config() };
+        let result = IslandModel::uniform(config, 2, migration(1, 1, Topology::Ring), data_set(), data_set()).unwrap().run();
+        assert!(!result.pareto_front.is_empty());
+        for a in &result.pareto_front {
+            assert!(result.pareto_front.iter().all(|b| !crate::ec::nsga2::dominates(&b.objectives(), &a.objectives())));
+        }
+    }
+}
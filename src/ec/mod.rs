@@ -1,2 +1,15 @@
 pub mod evolutionary_computation;
-pub mod contestant;
\ No newline at end of file
+pub mod contestant;
+pub mod evaluation_context;
+pub mod config;
+pub mod random;
+pub mod selection;
+pub mod nsga2;
+pub mod checkpoint;
+pub mod stats;
+pub mod fitness;
+pub mod genome;
+pub mod cache;
+pub mod hall_of_fame;
+pub mod island;
+pub mod speciation;
@@ -0,0 +1,138 @@
+use rand::Rng;
+use rand::rngs::StdRng;
+
+// all objectives are minimized, `a` dominates `b` if it is nowhere worse and somewhere better
+pub fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a <= b) && a.iter().zip(b).any(|(a, b)| a < b)
+}
+
+// splits the indices of `objectives` into fronts, front 0 is dominated by nobody,
+// front 1 only by front 0 and so on
+pub fn non_dominated_sort(objectives: &[Vec<f64>]) -> Vec<Vec<usize>> {
+    let n = objectives.len();
+    let mut dominated: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut domination_count: Vec<usize> = vec![0; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            if dominates(&objectives[i], &objectives[j]) {
+                dominated[i].push(j);
+                domination_count[j] += 1;
+            } else if dominates(&objectives[j], &objectives[i]) {
+                dominated[j].push(i);
+                domination_count[i] += 1;
+            }
+        }
+    }
+
+    let mut fronts: Vec<Vec<usize>> = Vec::new();
+    let mut front: Vec<usize> = (0..n).filter(|i| domination_count[*i] == 0).collect();
+    while !front.is_empty() {
+        let mut next: Vec<usize> = Vec::new();
+        for i in &front {
+            for j in &dominated[*i] {
+                domination_count[*j] -= 1;
+                if domination_count[*j] == 0 {
+                    next.push(*j);
+                }
+            }
+        }
+        fronts.push(front);
+        front = next;
+    }
+    fronts
+}
+
+// crowding distance of every member of `front` (same order), the extremes of every objective get infinity
+pub fn crowding_distance(objectives: &[Vec<f64>], front: &[usize]) -> Vec<f64> {
+    let mut distance = vec![0.0; front.len()];
+    if front.is_empty() {
+        return distance;
+    }
+    for (m, _) in objectives[front[0]].iter().enumerate() {
+        let value = |k: usize| objectives[front[k]][m];
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|a, b| value(*a).total_cmp(&value(*b)));
+        let (min, max) = (value(order[0]), value(order[order.len() - 1]));
+        distance[order[0]] = f64::INFINITY;
+        distance[order[order.len() - 1]] = f64::INFINITY;
+        if max - min <= 0.0 || !(max - min).is_finite() {
+            continue;
+        }
+        for k in 1..order.len().saturating_sub(1) {
+            distance[order[k]] += (value(order[k + 1]) - value(order[k - 1])) / (max - min);
+        }
+    }
+    distance
+}
+
+// binary tournament, the lower front wins, the less crowded one on a tie
+pub fn crowded_tournament(ranks: &[usize], distances: &[f64], rng: &mut StdRng) -> usize {
+    let a = rng.gen_range(0..ranks.len());
+    let b = rng.gen_range(0..ranks.len());
+    if ranks[a] < ranks[b] || (ranks[a] == ranks[b] && distances[a] >= distances[b]) {
+        a
+    } else {
+        b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use super::*;
+
+    // 0, 1, 2 and the copy 5 of 1 are the pareto front, 3 is only beaten by 1 and 5, 4 by everyone
+    fn objectives() -> Vec<Vec<f64>> {
+        vec![vec![1.0, 5.0], vec![2.0, 3.0], vec![4.0, 1.0], vec![3.0, 4.0], vec![5.0, 5.0], vec![2.0, 3.0]]
+    }
+
+    #[test]
+    fn domination_needs_one_strictly_better_objective() {
+        assert!(dominates(&[1.0, 2.0], &[1.0, 3.0]));
+        assert!(!dominates(&[1.0, 3.0], &[1.0, 2.0]));
+        assert!(!dominates(&[1.0, 2.0], &[1.0, 2.0]));
+        assert!(!dominates(&[1.0, 5.0], &[2.0, 3.0]));
+    }
+
+    #[test]
+    fn fronts_of_a_hand_made_population() {
+        assert_eq!(non_dominated_sort(&objectives()), vec![vec![0, 1, 2, 5], vec![3], vec![4]]);
+        assert!(non_dominated_sort(&[]).is_empty());
+    }
+
+    #[test]
+    fn crowding_distance_of_a_hand_made_front() {
+        let objectives = objectives();
+        // the middle one spans the whole range in both objectives
+        assert_eq!(crowding_distance(&objectives, &[0, 1, 2]), vec![f64::INFINITY, 2.0, f64::INFINITY]);
+
+        let line = vec![vec![0.0, 4.0], vec![1.0, 2.0], vec![3.0, 1.0], vec![4.0, 0.0]];
+        // (3 - 0) / 4 + (4 - 1) / 4 and (4 - 1) / 4 + (2 - 0) / 4
+        assert_eq!(crowding_distance(&line, &[0, 1, 2, 3]), vec![f64::INFINITY, 1.5, 1.25, f64::INFINITY]);
+        // the order of the front doesn't matter
+        assert_eq!(crowding_distance(&line, &[2, 3, 0, 1]), vec![1.25, f64::INFINITY, f64::INFINITY, 1.5]);
+        assert!(crowding_distance(&line, &[]).is_empty());
+    }
+
+    #[test]
+    fn equal_objectives_have_no_crowding_distance() {
+        let same = vec![vec![1.0, 1.0]; 3];
+        assert_eq!(crowding_distance(&same, &[0, 1, 2]), vec![f64::INFINITY, 0.0, f64::INFINITY]);
+    }
+
+    #[test]
+    fn crowded_tournament_prefers_lower_fronts_then_less_crowding() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut wins = [0; 2];
+        // the worse one only wins if it is drawn twice
+        for _ in 0..400 {
+            wins[crowded_tournament(&[0, 1], &[0.0, f64::INFINITY], &mut rng)] += 1;
+        }
+        assert!(wins[1] > 50 && wins[1] < 150, "{:?}", wins);
+        let mut wins = [0; 2];
+        for _ in 0..400 {
+            wins[crowded_tournament(&[0, 0], &[0.5, f64::INFINITY], &mut rng)] += 1;
+        }
+        assert!(wins[0] > 50 && wins[0] < 150, "{:?}", wins);
+    }
+}
@@ -0,0 +1,30 @@
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+// splitmix64 over the seed and the stream number, so every stream gets an independent seed
+pub fn derive_seed(seed: u64, stream: u64) -> u64 {
+    let mut z = seed ^ stream.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub fn stream_rng(seed: u64, stream: u64) -> StdRng {
+    StdRng::seed_from_u64(derive_seed(seed, stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use super::*;
+
+    #[test]
+    fn streams_are_reproducible_and_independent() {
+        assert_eq!(derive_seed(1, 2), derive_seed(1, 2));
+        assert_ne!(derive_seed(1, 2), derive_seed(1, 3));
+        assert_ne!(derive_seed(1, 2), derive_seed(2, 2));
+        let draw = |seed, stream| stream_rng(seed, stream).gen::<u64>();
+        assert_eq!(draw(5, 0), draw(5, 0));
+        assert_ne!(draw(5, 0), draw(5, 1));
+    }
+}
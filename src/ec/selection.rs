@@ -0,0 +1,275 @@
+use std::fmt::Debug;
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SelectionConfig {
+    // fitness proportionate
+    #[default]
+    Roulette,
+    // best of `size` random contestants
+    Tournament { size: usize },
+    // probabilities grow linearly with the rank, `pressure` (1..=2) is the expected
+    // number of picks of the best contestant
+    LinearRank { pressure: f64 },
+    // uniform over the best `fraction` of the population
+    Truncation { fraction: f64 },
+    // fitness proportionate with evenly spaced pointers, less noisy than roulette
+    StochasticUniversalSampling,
+}
+
+impl SelectionConfig {
+    pub fn build(&self) -> Box<dyn SelectionStrategy> {
+        match *self {
+            SelectionConfig::Roulette => Box::new(Roulette),
+            SelectionConfig::Tournament { size } => Box::new(Tournament { size }),
+            SelectionConfig::LinearRank { pressure } => Box::new(LinearRank { pressure }),
+            SelectionConfig::Truncation { fraction } => Box::new(Truncation { fraction }),
+            SelectionConfig::StochasticUniversalSampling => Box::new(StochasticUniversalSampling),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            SelectionConfig::Tournament { size: 0 } => Err("tournament size must be at least 1".to_string()),
+            SelectionConfig::LinearRank { pressure } if !(1.0..=2.0).contains(&pressure) => Err(format!("rank selection pressure must be in 1..=2, got {}", pressure)),
+            SelectionConfig::Truncation { fraction } if !(fraction > 0.0 && fraction <= 1.0) => Err(format!("truncation fraction must be in (0, 1], got {}", fraction)),
+            _ => Ok(()),
+        }
+    }
+}
+
+// picks parents by fitness (higher is better, never negative)
+pub trait SelectionStrategy: Debug + Send + Sync {
+    // indices of `count` parents, the same contestant can be picked more than once
+    fn select(&self, fitness: &[f64], count: usize, rng: &mut StdRng) -> Vec<usize>;
+
+    fn config(&self) -> SelectionConfig;
+
+    fn box_clone(&self) -> Box<dyn SelectionStrategy>;
+}
+
+impl Clone for Box<dyn SelectionStrategy> {
+    fn clone(&self) -> Box<dyn SelectionStrategy> {
+        self.box_clone()
+    }
+}
+
+// indices from the best to the worst contestant
+fn ranked(fitness: &[f64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..fitness.len()).collect();
+    order.sort_by(|a, b| fitness[*b].total_cmp(&fitness[*a]));
+    order
+}
+
+// index of the contestant whose slice of the cumulative weights contains `goal`
+fn pick_weighted(weights: &[f64], goal: f64) -> usize {
+    let mut current_sum = 0.0;
+    for (i, weight) in weights.iter().enumerate() {
+        current_sum += weight;
+        if goal < current_sum {
+            return i;
+        }
+    }
+    weights.len() - 1
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Roulette;
+
+impl SelectionStrategy for Roulette {
+    fn select(&self, fitness: &[f64], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        let fitness_sum: f64 = fitness.iter().sum();
+        if !(fitness_sum > 0.0 && fitness_sum.is_finite()) {
+            return (0..count).map(|_| rng.gen_range(0..fitness.len())).collect();
+        }
+        (0..count).map(|_| pick_weighted(fitness, rng.gen_range(0.0..fitness_sum))).collect()
+    }
+
+    fn config(&self) -> SelectionConfig {
+        SelectionConfig::Roulette
+    }
+
+    fn box_clone(&self) -> Box<dyn SelectionStrategy> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tournament {
+    pub size: usize,
+}
+
+impl SelectionStrategy for Tournament {
+    fn select(&self, fitness: &[f64], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        (0..count).map(|_| {
+            (0..self.size.max(1)).map(|_| rng.gen_range(0..fitness.len()))
+                .max_by(|a, b| fitness[*a].total_cmp(&fitness[*b]))
+                .unwrap()
+        }).collect()
+    }
+
+    fn config(&self) -> SelectionConfig {
+        SelectionConfig::Tournament { size: self.size }
+    }
+
+    fn box_clone(&self) -> Box<dyn SelectionStrategy> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LinearRank {
+    pub pressure: f64,
+}
+
+impl SelectionStrategy for LinearRank {
+    fn select(&self, fitness: &[f64], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        let order = ranked(fitness);
+        let n = order.len() as f64;
+        if order.len() == 1 {
+            return vec![order[0]; count];
+        }
+        // weight of the best is `pressure`, of the worst `2 - pressure`, they sum up to n
+        let weights: Vec<f64> = (0..order.len())
+            .map(|rank| self.pressure - (2.0 * self.pressure - 2.0) * rank as f64 / (n - 1.0))
+            .collect();
+        (0..count).map(|_| order[pick_weighted(&weights, rng.gen_range(0.0..n))]).collect()
+    }
+
+    fn config(&self) -> SelectionConfig {
+        SelectionConfig::LinearRank { pressure: self.pressure }
+    }
+
+    fn box_clone(&self) -> Box<dyn SelectionStrategy> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Truncation {
+    pub fraction: f64,
+}
+
+impl SelectionStrategy for Truncation {
+    fn select(&self, fitness: &[f64], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        let order = ranked(fitness);
+        let kept = ((order.len() as f64 * self.fraction).ceil() as usize).clamp(1, order.len());
+        (0..count).map(|_| order[rng.gen_range(0..kept)]).collect()
+    }
+
+    fn config(&self) -> SelectionConfig {
+        SelectionConfig::Truncation { fraction: self.fraction }
+    }
+
+    fn box_clone(&self) -> Box<dyn SelectionStrategy> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StochasticUniversalSampling;
+
+impl SelectionStrategy for StochasticUniversalSampling {
+    fn select(&self, fitness: &[f64], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        let fitness_sum: f64 = fitness.iter().sum();
+        if count == 0 {
+            return Vec::new();
+        }
+        if !(fitness_sum > 0.0 && fitness_sum.is_finite()) {
+            return (0..count).map(|_| rng.gen_range(0..fitness.len())).collect();
+        }
+        let distance = fitness_sum / count as f64;
+        let start = rng.gen_range(0.0..distance);
+        let mut selected: Vec<usize> = Vec::with_capacity(count);
+        let (mut point, mut current_sum) = (0, fitness[0]);
+        for i in 0..count {
+            let pointer = start + i as f64 * distance;
+            while current_sum <= pointer && point < fitness.len() - 1 {
+                point += 1;
+                current_sum += fitness[point];
+            }
+            selected.push(point);
+        }
+        // the pointers pick the parents in population order, mix them so pairs are random
+        selected.shuffle(rng);
+        selected
+    }
+
+    fn config(&self) -> SelectionConfig {
+        SelectionConfig::StochasticUniversalSampling
+    }
+
+    fn box_clone(&self) -> Box<dyn SelectionStrategy> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use super::*;
+
+    const ALL: [SelectionConfig; 5] = [
+        SelectionConfig::Roulette,
+        SelectionConfig::Tournament { size: 3 },
+        SelectionConfig::LinearRank { pressure: 1.5 },
+        SelectionConfig::Truncation { fraction: 0.5 },
+        SelectionConfig::StochasticUniversalSampling,
+    ];
+
+    // how often every contestant got picked
+    fn picks(config: SelectionConfig, fitness: &[f64], count: usize) -> Vec<usize> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut picks = vec![0; fitness.len()];
+        for i in config.build().select(fitness, count, &mut rng) {
+            picks[i] += 1;
+        }
+        picks
+    }
+
+    #[test]
+    fn every_strategy_picks_count_valid_indices() {
+        for config in ALL {
+            assert!(config.validate().is_ok());
+            assert_eq!(config.build().config(), config);
+            for fitness in [vec![0.5, 2.0, 1.0, 0.0, 3.0], vec![0.0; 5], vec![1.0]] {
+                assert_eq!(picks(config, &fitness, 7).iter().sum::<usize>(), 7, "{:?} with {:?}", config, fitness);
+            }
+        }
+    }
+
+    #[test]
+    fn fitness_proportionate_strategies_skip_zero_fitness() {
+        assert_eq!(picks(SelectionConfig::Roulette, &[0.0, 1.0, 0.0], 20), vec![0, 20, 0]);
+        // evenly spaced pointers pick every contestant exactly in proportion to its fitness
+        assert_eq!(picks(SelectionConfig::StochasticUniversalSampling, &[1.0, 0.0, 1.0, 2.0], 8), vec![2, 0, 2, 4]);
+    }
+
+    #[test]
+    fn rank_based_strategies_favour_the_best() {
+        let fitness = [1.0, 4.0, 3.0, 2.0];
+        // only the better half
+        let truncation = picks(SelectionConfig::Truncation { fraction: 0.5 }, &fitness, 100);
+        assert_eq!(truncation[0] + truncation[3], 0);
+        // the worst contestant has weight 2 - pressure = 0
+        let rank = picks(SelectionConfig::LinearRank { pressure: 2.0 }, &fitness, 1000);
+        assert_eq!(rank[0], 0);
+        assert!(rank[1] > rank[2] && rank[2] > rank[3]);
+        // a big tournament nearly always contains the best
+        let tournament = picks(SelectionConfig::Tournament { size: 30 }, &fitness, 100);
+        assert!(tournament[1] >= 99);
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        assert!(SelectionConfig::Tournament { size: 0 }.validate().is_err());
+        assert!(SelectionConfig::LinearRank { pressure: 0.5 }.validate().is_err());
+        assert!(SelectionConfig::LinearRank { pressure: 2.5 }.validate().is_err());
+        assert!(SelectionConfig::Truncation { fraction: 0.0 }.validate().is_err());
+        assert!(SelectionConfig::Truncation { fraction: 1.5 }.validate().is_err());
+        assert!(SelectionConfig::Truncation { fraction: f64::NAN }.validate().is_err());
+    }
+}
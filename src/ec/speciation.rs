@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use crate::ec::config::EcConfig;
+use crate::ec::contestant::Contestant;
+
+// contestants closer than `threshold` to the first member of a species belong to it and the fitness
+// of every contestant is divided by the size of its species (fitness sharing), so a new layout
+// doesn't have to beat a crowd of similar contestants on its own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeciationConfig {
+    pub threshold: f64,
+    // weights of the parts of the distance, every part is in 0..=1
+    pub layer_weight: f64,
+    pub epochs_weight: f64,
+    pub learning_rate_weight: f64,
+}
+
+impl Default for SpeciationConfig {
+    fn default() -> SpeciationConfig {
+        SpeciationConfig {
+            threshold: 0.3,
+            layer_weight: 1.0,
+            epochs_weight: 0.5,
+            learning_rate_weight: 0.5,
+        }
+    }
+}
+
+impl SpeciationConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.threshold > 0.0 && self.threshold.is_finite()) {
+            return Err(format!("speciation threshold must be positive, got {}", self.threshold));
+        }
+        let weights = [self.layer_weight, self.epochs_weight, self.learning_rate_weight];
+        if weights.iter().any(|w| !(*w >= 0.0 && w.is_finite())) || weights.iter().sum::<f64>() == 0.0 {
+            return Err(format!("speciation weights must not be negative and not all 0, got {:?}", weights));
+        }
+        Ok(())
+    }
+
+    // 0 for equal genomes, the differences are scaled by the bounds of the run: a slot that is used by only
+    // one of them counts 1, otherwise the difference of the sizes over max_nodes, averaged over the slots
+    pub fn distance(&self, a: &Contestant, b: &Contestant, bounds: &EcConfig) -> f64 {
+        let slots = a.layer_sizes.len().max(b.layer_sizes.len());
+        let mut layers = 0.0;
+        for i in 0..slots {
+            let x = a.layer_sizes.get(i).copied().unwrap_or(0);
+            let y = b.layer_sizes.get(i).copied().unwrap_or(0);
+            layers += if (x > 0) != (y > 0) { 1.0 } else { ((x - y).abs() as f64 / bounds.max_nodes as f64).min(1.0) };
+        }
+        if slots > 0 {
+            layers /= slots as f64;
+        }
+        let epochs = ((a.epochs - b.epochs).abs() as f64 / bounds.max_epochs as f64).min(1.0);
+        let learning_rate = ((a.learning_rate - b.learning_rate).abs() / bounds.max_learning_rate).min(1.0);
+        self.layer_weight * layers + self.epochs_weight * epochs + self.learning_rate_weight * learning_rate
+    }
+
+    // the species of every contestant, numbered in the order they were found, the first
+    // contestant that doesn't fit into any existing species founds a new one
+    pub fn species(&self, population: &[Contestant], bounds: &EcConfig) -> Vec<usize> {
+        let mut founders: Vec<usize> = Vec::new();
+        let mut species = Vec::new();
+        for (i, contestant) in population.iter().enumerate() {
+            match founders.iter().position(|f| self.distance(&population[*f], contestant, bounds) < self.threshold) {
+                Some(s) => species.push(s),
+                None => {
+                    species.push(founders.len());
+                    founders.push(i);
+                }
+            }
+        }
+        species
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::ec::contestant::Contestant;
+
+const SUMMARY_NAMES: [&str; 6] = ["cost", "fitness", "layer_count", "calculations", "epochs", "learning_rate"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Summary {
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+    // of the population, not of a sample
+    pub stddev: f64,
+}
+
+impl Summary {
+    pub fn of(values: &[f64]) -> Summary {
+        if values.is_empty() {
+            return Summary::default();
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        Summary {
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            mean,
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            stddev: (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt(),
+        }
+    }
+}
+
+// one record per generation, taken after the evaluation and the fitness assignment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationStats {
+    pub generation: i32,
+    pub cost: Summary,
+    pub fitness: Summary,
+    // hidden layers
+    pub layer_count: Summary,
+    pub calculations: Summary,
+    pub epochs: Summary,
+    pub learning_rate: Summary,
+    // seconds for evaluating and breeding the generation
+    pub wall_time: f64,
+    // distinct layer layouts per contestant, 1 if all of them are different
+    pub diversity: f64,
+    // distinct species, 1 without speciation
+    pub species: usize,
+}
+
+impl GenerationStats {
+    pub fn new(generation: i32, population: &[Contestant], wall_time: Duration) -> GenerationStats {
+        let summary = |f: &dyn Fn(&Contestant) -> f64| Summary::of(&population.iter().map(f).collect::<Vec<f64>>());
+        let layouts: HashSet<&Vec<i32>> = population.iter().map(|c| &c.layers).collect();
+        let species: HashSet<usize> = population.iter().map(|c| c.species).collect();
+        GenerationStats {
+            generation,
+            cost: summary(&|c| c.cost),
+            fitness: summary(&|c| c.fitness),
+            layer_count: summary(&|c| c.layers.len().saturating_sub(2) as f64),
+            calculations: summary(&|c| c.calculations as f64),
+            epochs: summary(&|c| c.epochs as f64),
+            learning_rate: summary(&|c| c.learning_rate),
+            wall_time: wall_time.as_secs_f64(),
+            diversity: if population.is_empty() { 0.0 } else { layouts.len() as f64 / population.len() as f64 },
+            species: species.len(),
+        }
+    }
+
+    // in the order of SUMMARY_NAMES
+    fn summaries(&self) -> [Summary; 6] {
+        [self.cost, self.fitness, self.layer_count, self.calculations, self.epochs, self.learning_rate]
+    }
+}
+
+// gets called by EvolutionaryComputation::run after every generation
+pub trait GenerationObserver: Send {
+    fn observe(&mut self, stats: &GenerationStats);
+}
+
+impl<F: FnMut(&GenerationStats) + Send> GenerationObserver for F {
+    fn observe(&mut self, stats: &GenerationStats) {
+        self(stats)
+    }
+}
+
+// picks the format by the file extension: .csv or .jsonl, `append` continues an existing file
+pub fn stats_writer<P: AsRef<Path>>(path: P, append: bool) -> io::Result<Box<dyn GenerationObserver>> {
+    let path = path.as_ref();
+    let csv = match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => true,
+        Some("jsonl") => false,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("can't tell the format of '{}', expected a .csv or .jsonl file", path.display()))),
+    };
+    let exists = append && path.exists();
+    let file = OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(path)?;
+    if csv {
+        Ok(Box::new(CsvStatsWriter::new(file, !exists)?))
+    } else {
+        Ok(Box::new(JsonLinesStatsWriter::new(file)))
+    }
+}
+
+pub struct CsvStatsWriter {
+    file: BufWriter<File>,
+}
+
+impl CsvStatsWriter {
+    pub fn new(file: File, write_header: bool) -> io::Result<CsvStatsWriter> {
+        let mut file = BufWriter::new(file);
+        if write_header {
+            let mut header = vec!["generation".to_string(), "wall_time".to_string(), "diversity".to_string(), "species".to_string()];
+            for name in SUMMARY_NAMES {
+                for statistic in ["min", "mean", "max", "stddev"] {
+                    header.push(format!("{}_{}", name, statistic));
+                }
+            }
+            writeln!(file, "{}", header.join(","))?;
+            file.flush()?;
+        }
+        Ok(CsvStatsWriter { file })
+    }
+}
+
+impl GenerationObserver for CsvStatsWriter {
+    fn observe(&mut self, stats: &GenerationStats) {
+        let mut record = vec![stats.generation.to_string(), stats.wall_time.to_string(), stats.diversity.to_string(), stats.species.to_string()];
+        for s in stats.summaries() {
+            record.extend([s.min, s.mean, s.max, s.stddev].iter().map(|v| v.to_string()));
+        }
+        // flushed every generation, so the log of an interrupted run is complete
+        if let Err(e) = writeln!(self.file, "{}", record.join(",")).and_then(|_| self.file.flush()) {
+            println!("Could not write generation stats: {}", e);
+        }
+    }
+}
+
+pub struct JsonLinesStatsWriter {
+    file: BufWriter<File>,
+}
+
+impl JsonLinesStatsWriter {
+    pub fn new(file: File) -> JsonLinesStatsWriter {
+        JsonLinesStatsWriter { file: BufWriter::new(file) }
+    }
+}
+
+impl GenerationObserver for JsonLinesStatsWriter {
+    fn observe(&mut self, stats: &GenerationStats) {
+        let result = serde_json::to_string(stats).map_err(io::Error::from)
+            .and_then(|line| writeln!(self.file, "{}", line))
+            .and_then(|_| self.file.flush());
+        if let Err(e) = result {
+            println!("Could not write generation stats: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+    use crate::ec::evaluation_context::EvaluationContext;
+    use crate::ec::genome::Genome;
+    use crate::nn::activation::Activation;
+    use crate::nn::data_set::DataSet;
+    use crate::nn::optimizer::OptimizerConfig;
+
+    fn genome() -> Genome {
+        Genome { activations: vec![Activation::Sigmoid; 2], output_activation: Activation::Sigmoid, optimizer: OptimizerConfig::default(), batch_size: 1, l2: 0.0 }
+    }
+
+    fn contestant(context: &EvaluationContext, layer_sizes: Vec<i32>, cost: f64, fitness: f64) -> Contestant {
+        let mut contestant = Contestant::new(10, 1, layer_sizes, 0.5, genome(), context);
+        contestant.cost = cost;
+        contestant.fitness = fitness;
+        contestant
+    }
+
+    fn stats() -> GenerationStats {
+        let data_set = DataSet::new(vec![vec![0.0, 1.0]], vec![vec![1.0]]);
+        let context = EvaluationContext::new(data_set.clone(), data_set, 2, 1);
+        let population = [
+            contestant(&context, vec![7, 0], 1.0, 4.0),
+            contestant(&context, vec![0, 7], 2.0, 2.0),
+            contestant(&context, vec![7, 8], 6.0, 1.0),
+        ];
+        GenerationStats::new(4, &population, Duration::from_millis(1500))
+    }
+
+    #[test]
+    fn summaries_are_over_the_whole_population() {
+        assert_eq!(Summary::of(&[1.0, 2.0, 3.0, 4.0]), Summary { min: 1.0, mean: 2.5, max: 4.0, stddev: 1.25f64.sqrt() });
+        assert_eq!(Summary::of(&[]), Summary::default());
+    }
+
+    #[test]
+    fn generation_stats_of_a_hand_made_population() {
+        let stats = stats();
+        assert_eq!(stats.generation, 4);
+        assert_eq!(stats.cost, Summary::of(&[1.0, 2.0, 6.0]));
+        assert_eq!((stats.fitness.min, stats.fitness.max), (1.0, 4.0));
+        // [7, 0] and [0, 7] are the same layout with one hidden layer
+        assert_eq!(stats.layer_count, Summary::of(&[1.0, 1.0, 2.0]));
+        // weights and biases of 2 inputs, the hidden layers and 1 output
+        let one_layer = 2.0 * 7.0 + 7.0 + 7.0 + 1.0;
+        assert_eq!(stats.calculations, Summary::of(&[one_layer, one_layer, 2.0 * 7.0 + 7.0 + 7.0 * 8.0 + 8.0 + 8.0 + 1.0]));
+        assert_eq!(stats.learning_rate, Summary::of(&[0.5; 3]));
+        assert_eq!(stats.wall_time, 1.5);
+        assert_eq!(stats.diversity, 2.0 / 3.0);
+        // without speciation everybody is in species 0
+        assert_eq!(stats.species, 1);
+    }
+
+    #[test]
+    fn writers_append_csv_and_json_lines() {
+        let dir = std::env::temp_dir().join(format!("nn_rust_stats_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let stats = stats();
+
+        let csv = dir.join("stats.csv");
+        stats_writer(&csv, false).unwrap().observe(&stats);
+        stats_writer(&csv, true).unwrap().observe(&stats);
+        let contents = fs::read_to_string(&csv).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        // the appending writer doesn't repeat the header
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("generation,wall_time,diversity,species,cost_min,cost_mean,cost_max,cost_stddev,fitness_min"));
+        assert_eq!(lines[0].split(',').count(), 4 + 4 * SUMMARY_NAMES.len());
+        assert!(lines[1].starts_with("4,1.5,"));
+        assert_eq!(lines[1], lines[2]);
+
+        let jsonl = dir.join("stats.jsonl");
+        stats_writer(&jsonl, false).unwrap().observe(&stats);
+        let contents = fs::read_to_string(&jsonl).unwrap();
+        assert_eq!(serde_json::from_str::<GenerationStats>(contents.trim()).unwrap(), stats);
+
+        assert!(stats_writer(dir.join("stats.txt"), false).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use crate::nn::matrix::Matrix;
+
+const LEAKY_RELU_SLOPE: f64 = 0.01;
+// sqrt(2 / pi), used by the tanh approximation of GELU
+const GELU_SCALE: f64 = 0.7978845608028654;
+const GELU_CUBIC: f64 = 0.044715;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Activation {
+    Sigmoid,
+    Relu,
+    LeakyRelu,
+    Tanh,
+    Softplus,
+    Linear,
+    Softmax,
+    Gelu,
+}
+
+impl Activation {
+    pub const ALL: [Activation; 8] = [
+        Activation::Sigmoid,
+        Activation::Relu,
+        Activation::LeakyRelu,
+        Activation::Tanh,
+        Activation::Softplus,
+        Activation::Linear,
+        Activation::Softmax,
+        Activation::Gelu,
+    ];
+
+    // applies the activation in place, every column of `m` is treated as one sample
+    pub fn apply(&self, m: &mut Matrix) {
+        match self {
+            Activation::Sigmoid => m.sigmoid(),
+            Activation::Relu => m.matrix.apply(|x| *x = x.max(0.0)),
+            Activation::LeakyRelu => m.matrix.apply(|x| if *x < 0.0 { *x *= LEAKY_RELU_SLOPE }),
+            Activation::Tanh => m.matrix.apply(|x| *x = x.tanh()),
+            Activation::Softplus => m.matrix.apply(|x| *x = softplus(*x)),
+            Activation::Linear => {}
+            Activation::Softmax => {
+                for mut col in m.matrix.column_iter_mut() {
+                    let max = col.max();
+                    col.apply(|x| *x = (*x - max).exp());
+                    let sum = col.sum();
+                    col /= sum;
+                }
+            }
+            Activation::Gelu => m.matrix.apply(|x| {
+                let t = (GELU_SCALE * (*x + GELU_CUBIC * x.powi(3))).tanh();
+                *x = 0.5 * *x * (1.0 + t)
+            }),
+        }
+    }
+
+    // derivative of the activation evaluated element wise, `sum` is the value before and
+    // `activated` the value after applying the activation
+    // None for softmax, it has no element wise derivative, use `backprop` for it
+    pub fn derivative(&self, sum: &Matrix, activated: &Matrix) -> Option<Matrix> {
+        let m = match self {
+            Activation::Sigmoid => return Some(Matrix::dsigmoid(activated)),
+            Activation::Relu => sum.matrix.map(|x| if x > 0.0 { 1.0 } else { 0.0 }),
+            Activation::LeakyRelu => sum.matrix.map(|x| if x > 0.0 { 1.0 } else { LEAKY_RELU_SLOPE }),
+            Activation::Tanh => activated.matrix.map(|a| 1.0 - a * a),
+            Activation::Softplus => sum.matrix.map(|x| 1.0 / (1.0 + (-x).exp())),
+            Activation::Linear => sum.matrix.map(|_| 1.0),
+            Activation::Softmax => return None,
+            Activation::Gelu => sum.matrix.map(|x| {
+                let t = (GELU_SCALE * (x + GELU_CUBIC * x.powi(3))).tanh();
+                0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * GELU_SCALE * (1.0 + 3.0 * GELU_CUBIC * x * x)
+            }),
+        };
+        Some(Matrix::new_from_matrix(m))
+    }
+
+    // turns the error with respect to the activated values into the error with respect to
+    // the sums (chain rule through the activation)
+    pub fn backprop(&self, sum: &Matrix, activated: &Matrix, error: &Matrix) -> Matrix {
+        match self.derivative(sum, activated) {
+            Some(mut gradient) => {
+                gradient.multiply_with_matrix(error);
+                gradient
+            }
+            // softmax, every value of a column depends on all sums of that column
+            None => {
+                let mut result = error.clone();
+                for (j, mut col) in result.matrix.column_iter_mut().enumerate() {
+                    let a = activated.matrix.column(j);
+                    let dot = a.dot(&col);
+                    col.add_scalar_mut(-dot);
+                    col.component_mul_assign(&a);
+                }
+                result
+            }
+        }
+    }
+}
+
+fn softplus(x: f64) -> f64 {
+    // ln(1 + e^x) without overflowing for large x
+    x.max(0.0) + (-x.abs()).exp().ln_1p()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: f64 = 1e-6;
+
+    // backprop of `error` has to be the gradient of sum(error * activation(sum)), checked with central differences
+    #[test]
+    fn backprop_matches_central_differences() {
+        // no value at the kink of relu
+        let sum = Matrix::from_2d_array(vec![vec![0.4, -0.2], vec![-1.3, 0.9], vec![2.1, -3.0]]);
+        let error = Matrix::from_2d_array(vec![vec![0.5, 1.5], vec![-1.0, 0.3], vec![2.0, -0.7]]);
+        let weighted = |activation: Activation, sum: &Matrix| {
+            let mut activated = sum.clone();
+            activation.apply(&mut activated);
+            activated.matrix.component_mul(&error.matrix).sum()
+        };
+        for activation in Activation::ALL {
+            let mut activated = sum.clone();
+            activation.apply(&mut activated);
+            let gradient = activation.backprop(&sum, &activated, &error);
+            for i in 0..sum.rows {
+                for j in 0..sum.cols {
+                    let mut plus = sum.clone();
+                    let mut minus = sum.clone();
+                    plus.matrix[(i, j)] += STEP;
+                    minus.matrix[(i, j)] -= STEP;
+                    let numerical = (weighted(activation, &plus) - weighted(activation, &minus)) / (2.0 * STEP);
+                    assert!((gradient.matrix[(i, j)] - numerical).abs() < 1e-5,
+                            "{:?}: ({}, {}) has the gradient {}, the numerical one is {}", activation, i, j, gradient.matrix[(i, j)], numerical);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_softmax_has_no_element_wise_derivative() {
+        let sum = Matrix::from_2d_array(vec![vec![0.4], vec![-1.3]]);
+        for activation in Activation::ALL {
+            let mut activated = sum.clone();
+            activation.apply(&mut activated);
+            assert_eq!(activation.derivative(&sum, &activated).is_none(), activation == Activation::Softmax, "{:?}", activation);
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeSet;
+use std::fs;
+use crate::nn::data_set::{DataSet, DataSetError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    // starts at 0
+    Index(usize),
+    // needs a header
+    Name(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetEncoding {
+    // numeric targets stay a single value, anything else gets one-hot encoded
+    Auto,
+    OneHot,
+    Numeric,
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub has_header: bool,
+    pub delimiter: char,
+    pub quote: char,
+    // None uses every column except the target
+    pub feature_columns: Option<Vec<Column>>,
+    pub target_column: Column,
+    pub target_encoding: TargetEncoding,
+    // fixes the one-hot order (e.g. to the classes of the training set), sorted class names otherwise
+    pub class_names: Option<Vec<String>>,
+}
+
+impl CsvOptions {
+    pub fn new(target_column: Column) -> CsvOptions {
+        CsvOptions {
+            has_header: true,
+            delimiter: ',',
+            quote: '"',
+            feature_columns: None,
+            target_column,
+            target_encoding: TargetEncoding::Auto,
+            class_names: None,
+        }
+    }
+}
+
+struct Field {
+    value: String,
+    line: usize,
+    column: usize,
+}
+
+impl Field {
+    fn error(&self, message: String) -> DataSetError {
+        DataSetError::Parse { line: self.line, column: self.column, message }
+    }
+}
+
+impl DataSet {
+    pub fn from_csv(file_name: &str, options: &CsvOptions) -> Result<DataSet, DataSetError> {
+        let contents = fs::read_to_string(file_name)?;
+        DataSet::parse_csv(&contents, options)
+    }
+
+    pub fn parse_csv(contents: &str, options: &CsvOptions) -> Result<DataSet, DataSetError> {
+        let mut records = read_records(contents, options.delimiter, options.quote)?.into_iter();
+        let header: Option<Vec<Field>> = if options.has_header { records.next() } else { None };
+        let records: Vec<Vec<Field>> = records.collect();
+        let width = match header.as_ref().or(records.first()) {
+            Some(first) => first.len(),
+            None => return Ok(DataSet::new_empty()),
+        };
+
+        let resolve = |column: &Column| -> Result<usize, DataSetError> {
+            match column {
+                Column::Index(i) if *i < width => Ok(*i),
+                Column::Index(i) => Err(DataSetError::Parse { line: 1, column: 1, message: format!("column {} is out of range, there are {} columns", i, width) }),
+                Column::Name(name) => match &header {
+                    Some(header) => header.iter().position(|f| &f.value == name)
+                        .ok_or_else(|| DataSetError::Parse { line: 1, column: 1, message: format!("there is no column named '{}'", name) }),
+                    None => Err(DataSetError::Parse { line: 1, column: 1, message: format!("column '{}' can't be found without a header", name) }),
+                },
+            }
+        };
+        let target_column = resolve(&options.target_column)?;
+        let feature_columns: Vec<usize> = match &options.feature_columns {
+            Some(columns) => columns.iter().map(resolve).collect::<Result<_, _>>()?,
+            None => (0..width).filter(|i| *i != target_column).collect(),
+        };
+
+        let mut inputs: Vec<Vec<f64>> = Vec::new();
+        for record in &records {
+            if record.len() != width {
+                let first = &record[0];
+                return Err(first.error(format!("expected {} fields, got {}", width, record.len())));
+            }
+            let mut input = Vec::new();
+            for column in &feature_columns {
+                let field = &record[*column];
+                input.push(field.value.trim().parse::<f64>().map_err(|_| field.error(format!("invalid number '{}'", field.value)))?);
+            }
+            inputs.push(input);
+        }
+
+        let target_fields: Vec<&Field> = records.iter().map(|record| &record[target_column]).collect();
+        let numeric = target_fields.iter().all(|f| f.value.trim().parse::<f64>().is_ok());
+        let one_hot = match options.target_encoding {
+            TargetEncoding::Auto => !numeric || options.class_names.is_some(),
+            TargetEncoding::OneHot => true,
+            TargetEncoding::Numeric => false,
+        };
+
+        if !one_hot {
+            let mut targets: Vec<Vec<f64>> = Vec::new();
+            for field in target_fields {
+                targets.push(vec![field.value.trim().parse::<f64>().map_err(|_| field.error(format!("invalid number '{}'", field.value)))?]);
+            }
+            return Ok(DataSet::new(inputs, targets));
+        }
+
+        let class_names: Vec<String> = match &options.class_names {
+            Some(names) => names.clone(),
+            None => target_fields.iter().map(|f| f.value.trim().to_string()).collect::<BTreeSet<String>>().into_iter().collect(),
+        };
+        let mut targets: Vec<Vec<f64>> = Vec::new();
+        for field in target_fields {
+            let index = class_names.iter().position(|name| name == field.value.trim())
+                .ok_or_else(|| field.error(format!("unknown class '{}'", field.value)))?;
+            let mut target = vec![0.0; class_names.len()];
+            target[index] = 1.0;
+            targets.push(target);
+        }
+        let mut data_set = DataSet::new(inputs, targets);
+        data_set.class_names = class_names;
+        Ok(data_set)
+    }
+}
+
+// splits the contents into records of fields, quoted fields may contain delimiters, line breaks
+// and doubled quotes, empty lines are skipped
+fn read_records(contents: &str, delimiter: char, quote: char) -> Result<Vec<Vec<Field>>, DataSetError> {
+    let mut records: Vec<Vec<Field>> = Vec::new();
+    let mut record: Vec<Field> = Vec::new();
+    let mut chars = contents.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+
+    loop {
+        let mut field = Field { value: String::new(), line, column };
+        let mut quoted = false;
+        if chars.peek() == Some(&quote) {
+            chars.next();
+            column += 1;
+            quoted = true;
+        }
+        // read until the end of the field
+        let end = loop {
+            match chars.next() {
+                None if quoted => return Err(field.error("quoted field is never closed".to_string())),
+                None => break None,
+                Some(c) if quoted && c == quote => {
+                    column += 1;
+                    if chars.peek() == Some(&quote) {
+                        chars.next();
+                        column += 1;
+                        field.value.push(quote);
+                    } else {
+                        quoted = false;
+                        match chars.peek() {
+                            Some(c) if *c != delimiter && *c != '\n' && *c != '\r' => {
+                                return Err(DataSetError::Parse { line, column, message: format!("expected '{}' or the end of the line after a quoted field", delimiter) });
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Some('\n') if !quoted => break Some('\n'),
+                Some('\r') if !quoted && chars.peek() == Some(&'\n') => {
+                    chars.next();
+                    break Some('\n');
+                }
+                Some(c) if !quoted && c == delimiter => break Some(c),
+                Some(c) => {
+                    if c == '\n' {
+                        line += 1;
+                        column = 1;
+                    } else {
+                        column += 1;
+                    }
+                    field.value.push(c);
+                }
+            }
+        };
+        record.push(field);
+        match end {
+            Some('\n') | None => {
+                let empty = record.len() == 1 && record[0].value.trim().is_empty();
+                if !empty {
+                    records.push(std::mem::take(&mut record));
+                } else {
+                    record.clear();
+                }
+                if end.is_none() {
+                    return Ok(records);
+                }
+                line += 1;
+                column = 1;
+            }
+            Some(_) => column += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(contents: &str, options: &CsvOptions) -> (usize, usize, String) {
+        match DataSet::parse_csv(contents, options) {
+            Err(DataSetError::Parse { line, column, message }) => (line, column, message),
+            other => panic!("expected a parse error for {:?}, got {:?}", contents, other),
+        }
+    }
+
+    #[test]
+    fn quoted_fields_keep_delimiters_and_quotes() {
+        let contents = "width,\"height, in cm\",name\n1,2,\"red, \"\"dark\"\"\"\n3,\"4\",blue\n";
+        let data_set = DataSet::parse_csv(contents, &CsvOptions::new(Column::Name("name".to_string()))).unwrap();
+        assert_eq!(data_set.inputs, vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        assert_eq!(data_set.class_names, vec!["blue".to_string(), "red, \"dark\"".to_string()]);
+        assert_eq!(data_set.targets, vec![vec![0.0, 1.0], vec![1.0, 0.0]]);
+
+        let options = CsvOptions {
+            feature_columns: Some(vec![Column::Name("height, in cm".to_string())]),
+            ..CsvOptions::new(Column::Index(2))
+        };
+        assert_eq!(DataSet::parse_csv(contents, &options).unwrap().inputs, vec![vec![2.0], vec![4.0]]);
+    }
+
+    #[test]
+    fn quoted_fields_can_span_lines() {
+        let contents = "a,b\n\"first\nline\",1\n\"x\",2\n";
+        let options = CsvOptions {
+            feature_columns: Some(vec![Column::Index(1)]),
+            ..CsvOptions::new(Column::Index(0))
+        };
+        let data_set = DataSet::parse_csv(contents, &options).unwrap();
+        assert_eq!(data_set.class_names, vec!["first\nline".to_string(), "x".to_string()]);
+        // the line numbers of later records count the line break inside the quotes
+        let contents = "a,b\n\"first\nline\",1\n\"x\",y\n";
+        let options = CsvOptions::new(Column::Index(0));
+        assert_eq!(parse_error(contents, &options), (4, 5, "invalid number 'y'".to_string()));
+    }
+
+    #[test]
+    fn other_delimiters_without_header() {
+        let contents = "1;2;0.5\r\n\r\n3;4;1.5\r\n";
+        let options = CsvOptions {
+            has_header: false,
+            delimiter: ';',
+            ..CsvOptions::new(Column::Index(2))
+        };
+        let data_set = DataSet::parse_csv(contents, &options).unwrap();
+        assert_eq!(data_set.inputs, vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        assert_eq!(data_set.targets, vec![vec![0.5], vec![1.5]]);
+        assert!(data_set.class_names.is_empty());
+    }
+
+    #[test]
+    fn target_encodings() {
+        let contents = "x,y\n1,2\n2,0\n";
+        let one_hot = CsvOptions { target_encoding: TargetEncoding::OneHot, ..CsvOptions::new(Column::Index(1)) };
+        assert_eq!(DataSet::parse_csv(contents, &one_hot).unwrap().targets, vec![vec![0.0, 1.0], vec![1.0, 0.0]]);
+
+        // the given class names fix the order and reject unknown classes
+        let classes = CsvOptions {
+            class_names: Some(vec!["2".to_string(), "1".to_string(), "0".to_string()]),
+            ..CsvOptions::new(Column::Index(1))
+        };
+        assert_eq!(DataSet::parse_csv(contents, &classes).unwrap().targets, vec![vec![1.0, 0.0, 0.0], vec![0.0, 0.0, 1.0]]);
+        let classes = CsvOptions { class_names: Some(vec!["2".to_string()]), ..classes };
+        assert_eq!(parse_error(contents, &classes), (3, 3, "unknown class '0'".to_string()));
+
+        let numeric = CsvOptions { target_encoding: TargetEncoding::Numeric, ..CsvOptions::new(Column::Index(0)) };
+        assert_eq!(parse_error("x,y\na,1\n", &numeric), (2, 1, "invalid number 'a'".to_string()));
+    }
+
+    #[test]
+    fn malformed_files_are_parse_errors() {
+        let options = CsvOptions::new(Column::Index(1));
+        assert_eq!(parse_error("a,b\n1,\"open\n", &options), (2, 3, "quoted field is never closed".to_string()));
+        assert_eq!(parse_error("a,b\n1,\"x\"y\n", &options), (2, 6, "expected ',' or the end of the line after a quoted field".to_string()));
+        assert_eq!(parse_error("a,b\n1,2\n3\n", &options), (3, 1, "expected 2 fields, got 1".to_string()));
+        assert_eq!(parse_error("a,b\n1,2\n", &CsvOptions::new(Column::Index(2))), (1, 1, "column 2 is out of range, there are 2 columns".to_string()));
+        assert_eq!(parse_error("a,b\n1,2\n", &CsvOptions::new(Column::Name("c".to_string()))), (1, 1, "there is no column named 'c'".to_string()));
+    }
+}
@@ -1,35 +1,301 @@
+use std::fmt;
+use std::fs;
+use std::iter::Peekable;
+use std::str::Chars;
 
 #[derive(Debug, Clone)]
 pub struct DataSet {
     pub inputs: Vec<Vec<f64>>,
     pub targets: Vec<Vec<f64>>,
+    // name of the class behind every one-hot target index, empty if the classes have no names
+    pub class_names: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum DataSetError {
+    Io(std::io::Error),
+    // line and column start at 1
+    Parse { line: usize, column: usize, message: String },
+    // binary files that don't match their format
+    Format(String),
+}
+
+impl fmt::Display for DataSetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataSetError::Io(e) => write!(f, "io error: {}", e),
+            DataSetError::Parse { line, column, message } => write!(f, "parse error at line {}, column {}: {}", line, column, message),
+            DataSetError::Format(message) => write!(f, "invalid file: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for DataSetError {}
+
+impl From<std::io::Error> for DataSetError {
+    fn from(e: std::io::Error) -> DataSetError {
+        DataSetError::Io(e)
+    }
 }
 
 impl DataSet {
     pub fn new(inputs: Vec<Vec<f64>>, targets: Vec<Vec<f64>>) -> DataSet {
-        DataSet { inputs, targets }
+        DataSet { inputs, targets, class_names: Vec::new() }
     }
 
     pub fn new_empty() -> DataSet {
-        DataSet { inputs: Vec::new(), targets: Vec::new() }
+        DataSet { inputs: Vec::new(), targets: Vec::new(), class_names: Vec::new() }
     }
 
-    pub fn get_from_file(file_name: &str) -> DataSet {
-        let contents = include_str!("data.txt");
-        // format: {input1.1, input1.2, input1.3, input1.4}, {target1.1, target1.2...}
-        let inputs: Vec<Vec<f64>> = contents
-            .split("},{")
-            .map(|x| x.replace("{", "").replace("}", ""))
-            .map(|x| x.split(",").map(|x| x.replace(" ", "").parse::<f64>().unwrap()).collect())
-            .collect();
+    // name of the class with the highest output
+    pub fn decode(&self, output: &[f64]) -> Option<&str> {
+        let (index, _) = output.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))?;
+        self.class_names.get(index).map(|name| name.as_str())
+    }
+
+    pub fn get_from_file(file_name: &str) -> Result<DataSet, DataSetError> {
+        let contents = fs::read_to_string(file_name)?;
+        DataSet::parse(&contents)
+    }
+
+    // format: every sample is a group of inputs followed by either a class index or a group of targets
+    //     {input1.1, input1.2, input1.3, input1.4}: 3
+    //     {input2.1, input2.2, input2.3, input2.4}, {target2.1, target2.2, ...}
+    // samples are separated by whitespace or commas, '#' starts a comment until the end of the line
+    // class indices get one-hot encoded, all samples have to use the same kind of target
+    pub fn parse(contents: &str) -> Result<DataSet, DataSetError> {
+        let mut parser = Parser::new(contents);
+        let mut inputs: Vec<Vec<f64>> = Vec::new();
         let mut targets: Vec<Vec<f64>> = Vec::new();
-        for i in 0..7 {
-            for __ in 0..inputs.len() / 7 {
-                let mut tar: Vec<f64> = (0..7).map(|_| 0.0).collect();
-                tar[i] = 1.0;
-                targets.push(tar);
+        let mut labels: Vec<usize> = Vec::new();
+
+        parser.skip_separators();
+        while parser.peek().is_some() {
+            let (line, column) = parser.position();
+            let input = parser.group()?;
+            if let Some(first) = inputs.first() {
+                if input.len() != first.len() {
+                    return Err(parser.error_at(line, column, format!("expected {} inputs, got {}", first.len(), input.len())));
+                }
+            }
+            inputs.push(input);
+
+            parser.skip_whitespace();
+            let (line, column) = parser.position();
+            match parser.peek() {
+                Some(':') if targets.is_empty() => {
+                    parser.next();
+                    parser.skip_whitespace();
+                    labels.push(parser.label()?);
+                }
+                Some(',') if labels.is_empty() => {
+                    parser.next();
+                    parser.skip_whitespace();
+                    let (line, column) = parser.position();
+                    let target = parser.group()?;
+                    if let Some(first) = targets.first() {
+                        if target.len() != first.len() {
+                            return Err(parser.error_at(line, column, format!("expected {} targets, got {}", first.len(), target.len())));
+                        }
+                    }
+                    targets.push(target);
+                }
+                Some(':') | Some(',') => return Err(parser.error_at(line, column, "class indices and target groups can't be mixed".to_string())),
+                _ => return Err(parser.error_at(line, column, "expected ':' followed by a class index or ',' followed by targets".to_string())),
+            }
+            parser.skip_separators();
+        }
+
+        if !labels.is_empty() {
+            let classes = labels.iter().max().unwrap() + 1;
+            targets = labels.iter().map(|label| {
+                let mut target = vec![0.0; classes];
+                target[*label] = 1.0;
+                target
+            }).collect();
+        }
+        Ok(DataSet::new(inputs, targets))
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(contents: &'a str) -> Parser<'a> {
+        Parser { chars: contents.chars().peekable(), line: 1, column: 1 }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else if c.is_some() {
+            self.column += 1;
+        }
+        c
+    }
+
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+
+    fn error_at(&self, line: usize, column: usize, message: String) -> DataSetError {
+        DataSetError::Parse { line, column, message }
+    }
+
+    fn error(&self, message: String) -> DataSetError {
+        self.error_at(self.line, self.column, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while self.peek().is_some() && self.peek() != Some('\n') {
+                    self.next();
+                }
+            } else if c.is_whitespace() {
+                self.next();
+            } else {
+                break;
             }
-        };
-        DataSet { inputs, targets }
+        }
     }
-}
\ No newline at end of file
+
+    fn skip_separators(&mut self) {
+        self.skip_whitespace();
+        while self.peek() == Some(',') {
+            self.next();
+            self.skip_whitespace();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), DataSetError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.next();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(format!("expected '{}', found end of file", expected))),
+        }
+    }
+
+    fn token(&mut self) -> String {
+        let mut token = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+' {
+                token.push(c);
+                self.next();
+            } else {
+                break;
+            }
+        }
+        token
+    }
+
+    // {number, number, ...}
+    fn group(&mut self) -> Result<Vec<f64>, DataSetError> {
+        self.expect('{')?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let (line, column) = self.position();
+            let token = self.token();
+            match token.parse::<f64>() {
+                Ok(value) => values.push(value),
+                Err(_) if token.is_empty() => return Err(self.error("expected a number".to_string())),
+                Err(_) => return Err(self.error_at(line, column, format!("invalid number '{}'", token))),
+            }
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.next();
+                }
+                Some('}') => {
+                    self.next();
+                    return Ok(values);
+                }
+                Some(c) => return Err(self.error(format!("expected ',' or '}}', found '{}'", c))),
+                None => return Err(self.error("expected ',' or '}', found end of file".to_string())),
+            }
+        }
+    }
+
+    fn label(&mut self) -> Result<usize, DataSetError> {
+        let (line, column) = self.position();
+        let token = self.token();
+        if token.is_empty() {
+            return Err(self.error("expected a class index".to_string()));
+        }
+        token.parse::<usize>().map_err(|_| self.error_at(line, column, format!("invalid class index '{}'", token)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // line, column and message of a parse error
+    fn parse_error(contents: &str) -> (usize, usize, String) {
+        match DataSet::parse(contents) {
+            Err(DataSetError::Parse { line, column, message }) => (line, column, message),
+            other => panic!("expected a parse error for {:?}, got {:?}", contents, other),
+        }
+    }
+
+    #[test]
+    fn class_indices_are_one_hot_encoded() {
+        let data_set = DataSet::parse("# comment\n{1, 2.5}: 2, {-3, 4e1}: 0 # trailing comment\n").unwrap();
+        assert_eq!(data_set.inputs, vec![vec![1.0, 2.5], vec![-3.0, 40.0]]);
+        assert_eq!(data_set.targets, vec![vec![0.0, 0.0, 1.0], vec![1.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    fn target_groups_are_taken_as_they_are() {
+        let data_set = DataSet::parse("{1, 2}, {0.5, 0.25}\n\n{3, 4}, {1, 0}").unwrap();
+        assert_eq!(data_set.inputs, vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        assert_eq!(data_set.targets, vec![vec![0.5, 0.25], vec![1.0, 0.0]]);
+    }
+
+    #[test]
+    fn empty_files_have_no_samples() {
+        let data_set = DataSet::parse("  # nothing here\n").unwrap();
+        assert!(data_set.inputs.is_empty() && data_set.targets.is_empty());
+    }
+
+    #[test]
+    fn the_shipped_data_file_parses() {
+        let data_set = DataSet::parse(include_str!("data.txt")).unwrap();
+        assert!(!data_set.inputs.is_empty());
+        assert_eq!(data_set.inputs.len(), data_set.targets.len());
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        assert_eq!(parse_error("{1, 2}: 0\n{1, x}: 1"), (2, 5, "invalid number 'x'".to_string()));
+        assert_eq!(parse_error("{1, 2}: 0\n  {1}: 1"), (2, 3, "expected 2 inputs, got 1".to_string()));
+        assert_eq!(parse_error("{1}, {1, 0}\n{2}, {1}"), (2, 6, "expected 2 targets, got 1".to_string()));
+        assert_eq!(parse_error("{1}: 0, {2}, {1}"), (1, 12, "class indices and target groups can't be mixed".to_string()));
+        assert_eq!(parse_error("{1}: a"), (1, 6, "invalid class index 'a'".to_string()));
+        assert_eq!(parse_error("{1} 0"), (1, 5, "expected ':' followed by a class index or ',' followed by targets".to_string()));
+        assert_eq!(parse_error("{1, 2"), (1, 6, "expected ',' or '}', found end of file".to_string()));
+        assert_eq!(parse_error("{1, }"), (1, 5, "expected a number".to_string()));
+        assert_eq!(parse_error("1: 0"), (1, 1, "expected '{', found '1'".to_string()));
+    }
+
+    #[test]
+    fn errors_display_their_position() {
+        let error = DataSet::parse("{1, x}: 1").unwrap_err();
+        assert_eq!(error.to_string(), "parse error at line 1, column 5: invalid number 'x'");
+        assert!(matches!(DataSet::get_from_file("does/not/exist.txt"), Err(DataSetError::Io(_))));
+    }
+}
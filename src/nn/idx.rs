@@ -0,0 +1,191 @@
+use std::fs;
+use std::io::Read;
+use flate2::read::GzDecoder;
+use crate::nn::data_set::{DataSet, DataSetError};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// contents of an IDX file (the format of the MNIST data set)
+#[derive(Debug, Clone)]
+pub struct IdxArray {
+    pub data_type: u8,
+    pub dimensions: Vec<usize>,
+    pub data: Vec<f64>,
+}
+
+impl IdxArray {
+    // reads plain or gzip compressed files
+    pub fn from_file(file_name: &str) -> Result<IdxArray, DataSetError> {
+        let mut bytes = fs::read(file_name)?;
+        if bytes.starts_with(&GZIP_MAGIC) {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(&bytes[..]).read_to_end(&mut decompressed)?;
+            bytes = decompressed;
+        }
+        IdxArray::parse(&bytes)
+    }
+
+    // header: two zero bytes, the data type, the number of dimensions,
+    // then every dimension as big endian u32, followed by the data (big endian)
+    pub fn parse(bytes: &[u8]) -> Result<IdxArray, DataSetError> {
+        if bytes.len() < 4 || bytes[0] != 0 || bytes[1] != 0 {
+            return Err(DataSetError::Format("not an IDX file".to_string()));
+        }
+        let data_type = bytes[2];
+        let value_size = match data_type {
+            0x08 | 0x09 => 1,
+            0x0B => 2,
+            0x0C | 0x0D => 4,
+            0x0E => 8,
+            t => return Err(DataSetError::Format(format!("unknown IDX data type 0x{:02X}", t))),
+        };
+        let dimension_count = bytes[3] as usize;
+        let header_size = 4 + dimension_count * 4;
+        if bytes.len() < header_size {
+            return Err(DataSetError::Format("IDX header is truncated".to_string()));
+        }
+        let dimensions: Vec<usize> = bytes[4..header_size].chunks(4)
+            .map(|d| u32::from_be_bytes([d[0], d[1], d[2], d[3]]) as usize)
+            .collect();
+        let size = checked_product(&dimensions).and_then(|count| count.checked_mul(value_size))
+            .ok_or_else(|| DataSetError::Format(format!("IDX dimensions {:?} are too large", dimensions)))?;
+        let body = &bytes[header_size..];
+        if body.len() != size {
+            return Err(DataSetError::Format(format!("expected {} bytes of data for dimensions {:?}, got {}", size, dimensions, body.len())));
+        }
+        let data: Vec<f64> = body.chunks(value_size).map(|v| match data_type {
+            0x08 => v[0] as f64,
+            0x09 => v[0] as i8 as f64,
+            0x0B => i16::from_be_bytes([v[0], v[1]]) as f64,
+            0x0C => i32::from_be_bytes([v[0], v[1], v[2], v[3]]) as f64,
+            0x0D => f32::from_be_bytes([v[0], v[1], v[2], v[3]]) as f64,
+            _ => f64::from_be_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]]),
+        }).collect();
+        Ok(IdxArray { data_type, dimensions, data })
+    }
+}
+
+// None if it doesn't fit into a usize
+fn checked_product(values: &[usize]) -> Option<usize> {
+    values.iter().try_fold(1usize, |product, value| product.checked_mul(*value))
+}
+
+impl DataSet {
+    // every image becomes one input row, unsigned byte pixels get scaled to 0..1, the labels get one-hot
+    // encoded into `classes` values (10 for MNIST), so training and test sets always get the same width
+    pub fn from_idx(images_file: &str, labels_file: &str, classes: usize) -> Result<DataSet, DataSetError> {
+        let images = IdxArray::from_file(images_file)?;
+        let labels = IdxArray::from_file(labels_file)?;
+        if images.dimensions.is_empty() || labels.dimensions.len() != 1 {
+            return Err(DataSetError::Format(format!("expected images with at least 1 dimension and labels with 1 dimension, got {:?} and {:?}", images.dimensions, labels.dimensions)));
+        }
+        if images.dimensions[0] != labels.dimensions[0] {
+            return Err(DataSetError::Format(format!("{} images but {} labels", images.dimensions[0], labels.dimensions[0])));
+        }
+        if let Some(label) = labels.data.iter().find(|l| **l < 0.0 || l.fract() != 0.0 || **l >= classes as f64) {
+            return Err(DataSetError::Format(format!("labels have to be integers in 0..{}, got {}", classes, label)));
+        }
+
+        let scale = if images.data_type == 0x08 { 1.0 / 255.0 } else { 1.0 };
+        // the product of all dimensions was checked by parse, this part of it can only overflow without images
+        let image_size = checked_product(&images.dimensions[1..])
+            .ok_or_else(|| DataSetError::Format(format!("IDX dimensions {:?} are too large", images.dimensions)))?;
+        let inputs: Vec<Vec<f64>> = if image_size == 0 {
+            vec![Vec::new(); images.dimensions[0]]
+        } else {
+            images.data.chunks(image_size).map(|image| image.iter().map(|p| p * scale).collect()).collect()
+        };
+        let targets: Vec<Vec<f64>> = labels.data.iter().map(|label| {
+            let mut target = vec![0.0; classes];
+            target[*label as usize] = 1.0;
+            target
+        }).collect();
+
+        let mut data_set = DataSet::new(inputs, targets);
+        data_set.class_names = (0..classes).map(|c| c.to_string()).collect();
+        Ok(data_set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(data_type: u8, dimensions: &[u32]) -> Vec<u8> {
+        let mut bytes = vec![0, 0, data_type, dimensions.len() as u8];
+        for d in dimensions {
+            bytes.extend_from_slice(&d.to_be_bytes());
+        }
+        bytes
+    }
+
+    fn format_error(bytes: &[u8]) -> String {
+        match IdxArray::parse(bytes) {
+            Err(DataSetError::Format(message)) => message,
+            other => panic!("expected a format error for {:?}, got {:?}", bytes, other),
+        }
+    }
+
+    #[test]
+    fn values_are_read_big_endian() {
+        let mut bytes = header(0x09, &[2]);
+        bytes.extend_from_slice(&[0x7F, 0xFF]);
+        assert_eq!(IdxArray::parse(&bytes).unwrap().data, vec![127.0, -1.0]);
+
+        let mut bytes = header(0x0B, &[1, 2]);
+        bytes.extend_from_slice(&[0x01, 0x00, 0xFF, 0xFE]);
+        let array = IdxArray::parse(&bytes).unwrap();
+        assert_eq!(array.dimensions, vec![1, 2]);
+        assert_eq!(array.data, vec![256.0, -2.0]);
+
+        let mut bytes = header(0x0D, &[1]);
+        bytes.extend_from_slice(&1.5f32.to_be_bytes());
+        assert_eq!(IdxArray::parse(&bytes).unwrap().data, vec![1.5]);
+    }
+
+    #[test]
+    fn malformed_headers_are_a_format_error() {
+        assert_eq!(format_error(&[0, 0, 0x08]), "not an IDX file");
+        assert_eq!(format_error(&[1, 0, 0x08, 0]), "not an IDX file");
+        assert_eq!(format_error(&header(0x0A, &[1])), "unknown IDX data type 0x0A");
+        // 3 dimensions announced, only 1 and a half there
+        let mut bytes = header(0x08, &[2, 2, 2]);
+        bytes.truncate(10);
+        assert_eq!(format_error(&bytes), "IDX header is truncated");
+    }
+
+    #[test]
+    fn data_has_to_match_the_dimensions() {
+        let mut bytes = header(0x0C, &[2]);
+        bytes.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(format_error(&bytes), "expected 8 bytes of data for dimensions [2], got 7");
+        bytes.extend_from_slice(&[2, 3]);
+        assert_eq!(format_error(&bytes), "expected 8 bytes of data for dimensions [2], got 9");
+    }
+
+    #[test]
+    fn dimensions_that_overflow_are_a_format_error() {
+        let bytes = header(0x0E, &[u32::MAX, u32::MAX, u32::MAX]);
+        assert!(matches!(IdxArray::parse(&bytes), Err(DataSetError::Format(_))));
+    }
+
+    #[test]
+    fn targets_have_the_given_number_of_classes() {
+        let dir = std::env::temp_dir().join(format!("nn_rust_idx_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let images = dir.join("images");
+        let labels = dir.join("labels");
+        let mut image_bytes = header(0x08, &[2, 1]);
+        image_bytes.extend_from_slice(&[0, 255]);
+        let mut label_bytes = header(0x08, &[2]);
+        label_bytes.extend_from_slice(&[0, 3]);
+        fs::write(&images, image_bytes).unwrap();
+        fs::write(&labels, label_bytes).unwrap();
+
+        let data_set = DataSet::from_idx(images.to_str().unwrap(), labels.to_str().unwrap(), 10).unwrap();
+        assert_eq!(data_set.targets[1].len(), 10);
+        assert_eq!(data_set.targets[1][3], 1.0);
+        assert!(matches!(DataSet::from_idx(images.to_str().unwrap(), labels.to_str().unwrap(), 3), Err(DataSetError::Format(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
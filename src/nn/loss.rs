@@ -0,0 +1,304 @@
+use std::fmt::Debug;
+use serde::{Deserialize, Serialize};
+use crate::nn::activation::Activation;
+use crate::nn::matrix::Matrix;
+
+// keeps the logarithms of the cross entropy losses finite
+const EPSILON: f64 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum LossConfig {
+    #[default]
+    MeanSquaredError,
+    MeanAbsoluteError,
+    Huber { delta: f64 },
+    BinaryCrossEntropy,
+    SoftmaxCrossEntropy,
+}
+
+impl LossConfig {
+    pub fn build(&self) -> Box<dyn Loss> {
+        match *self {
+            LossConfig::MeanSquaredError => Box::new(MeanSquaredError),
+            LossConfig::MeanAbsoluteError => Box::new(MeanAbsoluteError),
+            LossConfig::Huber { delta } => Box::new(Huber { delta }),
+            LossConfig::BinaryCrossEntropy => Box::new(BinaryCrossEntropy),
+            LossConfig::SoftmaxCrossEntropy => Box::new(SoftmaxCrossEntropy),
+        }
+    }
+}
+
+// every column of `output` and `target` is one sample, losses are averaged over the samples
+pub trait Loss: Debug + Send + Sync {
+    fn loss(&self, output: &Matrix, target: &Matrix) -> f64;
+
+    // derivative of the loss with respect to every output value
+    fn gradient(&self, output: &Matrix, target: &Matrix) -> Matrix;
+
+    // loss computed with access to the sums of the output layer,
+    // losses that can be fused with the output activation override this
+    fn output_loss(&self, _activation: Activation, _sum: &Matrix, output: &Matrix, target: &Matrix) -> f64 {
+        self.loss(output, target)
+    }
+
+    // derivative of the loss with respect to the sums of the output layer
+    fn output_gradient(&self, activation: Activation, sum: &Matrix, output: &Matrix, target: &Matrix) -> Matrix {
+        activation.backprop(sum, output, &self.gradient(output, target))
+    }
+
+    fn config(&self) -> LossConfig;
+
+    fn box_clone(&self) -> Box<dyn Loss>;
+}
+
+impl Clone for Box<dyn Loss> {
+    fn clone(&self) -> Box<dyn Loss> {
+        self.box_clone()
+    }
+}
+
+fn per_sample(output: &Matrix, target: &Matrix, f: impl Fn(f64, f64) -> f64) -> f64 {
+    if output.rows != target.rows || output.cols != target.cols {
+        panic!("Loss: output and target have different dimensions: {}x{} vs {}x{}",
+               output.rows, output.cols, target.rows, target.cols);
+    }
+    let sum: f64 = output.matrix.iter().zip(target.matrix.iter()).map(|(o, t)| f(*o, *t)).sum();
+    sum / output.cols as f64
+}
+
+fn element_wise(output: &Matrix, target: &Matrix, f: impl Fn(f64, f64) -> f64) -> Matrix {
+    Matrix::new_from_matrix(output.matrix.zip_map(&target.matrix, f))
+}
+
+// half of the squared error, so the gradient is just `output - target`
+#[derive(Debug, Clone, Copy)]
+pub struct MeanSquaredError;
+
+impl Loss for MeanSquaredError {
+    fn loss(&self, output: &Matrix, target: &Matrix) -> f64 {
+        per_sample(output, target, |o, t| 0.5 * (o - t).powi(2))
+    }
+
+    fn gradient(&self, output: &Matrix, target: &Matrix) -> Matrix {
+        Matrix::subtract(output, target)
+    }
+
+    fn config(&self) -> LossConfig {
+        LossConfig::MeanSquaredError
+    }
+
+    fn box_clone(&self) -> Box<dyn Loss> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MeanAbsoluteError;
+
+impl Loss for MeanAbsoluteError {
+    fn loss(&self, output: &Matrix, target: &Matrix) -> f64 {
+        per_sample(output, target, |o, t| (o - t).abs())
+    }
+
+    fn gradient(&self, output: &Matrix, target: &Matrix) -> Matrix {
+        element_wise(output, target, |o, t| if o > t { 1.0 } else if o < t { -1.0 } else { 0.0 })
+    }
+
+    fn config(&self) -> LossConfig {
+        LossConfig::MeanAbsoluteError
+    }
+
+    fn box_clone(&self) -> Box<dyn Loss> {
+        Box::new(*self)
+    }
+}
+
+// quadratic for errors smaller than `delta`, linear above
+#[derive(Debug, Clone, Copy)]
+pub struct Huber {
+    pub delta: f64,
+}
+
+impl Loss for Huber {
+    fn loss(&self, output: &Matrix, target: &Matrix) -> f64 {
+        per_sample(output, target, |o, t| {
+            let d = (o - t).abs();
+            if d <= self.delta { 0.5 * d * d } else { self.delta * (d - 0.5 * self.delta) }
+        })
+    }
+
+    fn gradient(&self, output: &Matrix, target: &Matrix) -> Matrix {
+        element_wise(output, target, |o, t| (o - t).clamp(-self.delta, self.delta))
+    }
+
+    fn config(&self) -> LossConfig {
+        LossConfig::Huber { delta: self.delta }
+    }
+
+    fn box_clone(&self) -> Box<dyn Loss> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BinaryCrossEntropy;
+
+impl Loss for BinaryCrossEntropy {
+    fn loss(&self, output: &Matrix, target: &Matrix) -> f64 {
+        per_sample(output, target, |o, t| {
+            let o = o.clamp(EPSILON, 1.0 - EPSILON);
+            -(t * o.ln() + (1.0 - t) * (1.0 - o).ln())
+        })
+    }
+
+    fn gradient(&self, output: &Matrix, target: &Matrix) -> Matrix {
+        element_wise(output, target, |o, t| {
+            let o = o.clamp(EPSILON, 1.0 - EPSILON);
+            (o - t) / (o * (1.0 - o))
+        })
+    }
+
+    fn output_loss(&self, activation: Activation, sum: &Matrix, output: &Matrix, target: &Matrix) -> f64 {
+        match activation {
+            // -t * ln(sigmoid(z)) - (1 - t) * ln(1 - sigmoid(z)) = softplus(z) - t * z
+            Activation::Sigmoid => per_sample(sum, target, |z, t| z.max(0.0) + (-z.abs()).exp().ln_1p() - t * z),
+            _ => self.loss(output, target),
+        }
+    }
+
+    fn output_gradient(&self, activation: Activation, sum: &Matrix, output: &Matrix, target: &Matrix) -> Matrix {
+        match activation {
+            Activation::Sigmoid => Matrix::subtract(output, target),
+            _ => activation.backprop(sum, output, &self.gradient(output, target)),
+        }
+    }
+
+    fn config(&self) -> LossConfig {
+        LossConfig::BinaryCrossEntropy
+    }
+
+    fn box_clone(&self) -> Box<dyn Loss> {
+        Box::new(*self)
+    }
+}
+
+// categorical cross entropy, meant to be used with a softmax output layer
+#[derive(Debug, Clone, Copy)]
+pub struct SoftmaxCrossEntropy;
+
+impl Loss for SoftmaxCrossEntropy {
+    fn loss(&self, output: &Matrix, target: &Matrix) -> f64 {
+        per_sample(output, target, |o, t| -t * o.max(EPSILON).ln())
+    }
+
+    fn gradient(&self, output: &Matrix, target: &Matrix) -> Matrix {
+        element_wise(output, target, |o, t| -t / o.max(EPSILON))
+    }
+
+    fn output_loss(&self, activation: Activation, sum: &Matrix, output: &Matrix, target: &Matrix) -> f64 {
+        match activation {
+            Activation::Softmax => {
+                // -sum(t * log_softmax(z)) computed straight from the sums
+                let mut total = 0.0;
+                for (z, t) in sum.matrix.column_iter().zip(target.matrix.column_iter()) {
+                    let max = z.max();
+                    let log_sum_exp = max + z.map(|x| (x - max).exp()).sum().ln();
+                    total -= z.iter().zip(t.iter()).map(|(z, t)| t * (z - log_sum_exp)).sum::<f64>();
+                }
+                total / sum.cols as f64
+            }
+            _ => self.loss(output, target),
+        }
+    }
+
+    fn output_gradient(&self, activation: Activation, sum: &Matrix, output: &Matrix, target: &Matrix) -> Matrix {
+        match activation {
+            Activation::Softmax => Matrix::subtract(output, target),
+            _ => activation.backprop(sum, output, &self.gradient(output, target)),
+        }
+    }
+
+    fn config(&self) -> LossConfig {
+        LossConfig::SoftmaxCrossEntropy
+    }
+
+    fn box_clone(&self) -> Box<dyn Loss> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: f64 = 1e-6;
+
+    fn column(values: &[f64]) -> Matrix {
+        Matrix::from_2d_array(values.iter().map(|v| vec![*v]).collect())
+    }
+
+    fn activated(activation: Activation, sum: &Matrix) -> Matrix {
+        let mut output = sum.clone();
+        activation.apply(&mut output);
+        output
+    }
+
+    // compares output_gradient with central differences of output_loss with respect to every sum
+    fn check_gradient(loss: &dyn Loss, activation: Activation, sum: &[f64], target: &[f64]) {
+        let sum = column(sum);
+        let target = column(target);
+        let gradient = loss.output_gradient(activation, &sum, &activated(activation, &sum), &target);
+        for i in 0..sum.rows {
+            let mut plus = sum.clone();
+            let mut minus = sum.clone();
+            plus.matrix[(i, 0)] += STEP;
+            minus.matrix[(i, 0)] -= STEP;
+            let numerical = (loss.output_loss(activation, &plus, &activated(activation, &plus), &target)
+                - loss.output_loss(activation, &minus, &activated(activation, &minus), &target)) / (2.0 * STEP);
+            assert!((gradient.matrix[(i, 0)] - numerical).abs() < 1e-5,
+                    "{:?} with {:?}: row {} has the gradient {}, the numerical one is {}", loss, activation, i, gradient.matrix[(i, 0)], numerical);
+        }
+    }
+
+    #[test]
+    fn mean_squared_error_gradient() {
+        check_gradient(&MeanSquaredError, Activation::Sigmoid, &[0.3, -1.2, 2.0], &[1.0, 0.0, 0.5]);
+        check_gradient(&MeanSquaredError, Activation::Linear, &[0.3, -1.2, 2.0], &[1.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn mean_absolute_error_gradient() {
+        check_gradient(&MeanAbsoluteError, Activation::Sigmoid, &[0.3, -1.2, 2.0], &[1.0, 0.0, 0.5]);
+        check_gradient(&MeanAbsoluteError, Activation::Tanh, &[0.3, -1.2, 2.0], &[1.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn huber_gradient() {
+        // the first two errors are in the linear part, the last one in the quadratic part
+        check_gradient(&Huber { delta: 0.5 }, Activation::Linear, &[2.0, -1.2, 0.7], &[1.0, 0.0, 0.5]);
+        check_gradient(&Huber { delta: 0.1 }, Activation::Sigmoid, &[0.3, -1.2, 0.0], &[1.0, 0.0, 0.52]);
+    }
+
+    #[test]
+    fn binary_cross_entropy_gradient() {
+        // fused with sigmoid and through the chain rule
+        check_gradient(&BinaryCrossEntropy, Activation::Sigmoid, &[0.3, -1.2, 2.0], &[1.0, 0.0, 0.5]);
+        check_gradient(&BinaryCrossEntropy, Activation::Softmax, &[0.3, -1.2, 2.0], &[1.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn softmax_cross_entropy_gradient() {
+        check_gradient(&SoftmaxCrossEntropy, Activation::Softmax, &[0.3, -1.2, 2.0], &[0.0, 0.0, 1.0]);
+        check_gradient(&SoftmaxCrossEntropy, Activation::Sigmoid, &[0.3, -1.2, 2.0], &[0.2, 0.3, 0.5]);
+    }
+
+    #[test]
+    fn fused_losses_match_the_plain_ones() {
+        let sum = column(&[0.3, -1.2, 2.0]);
+        let target = column(&[0.0, 0.0, 1.0]);
+        for (loss, activation) in [(&BinaryCrossEntropy as &dyn Loss, Activation::Sigmoid), (&SoftmaxCrossEntropy, Activation::Softmax)] {
+            let output = activated(activation, &sum);
+            assert!((loss.output_loss(activation, &sum, &output, &target) - loss.loss(&output, &target)).abs() < 1e-12);
+        }
+    }
+}
@@ -3,6 +3,7 @@ extern crate nalgebra as na;
 use std::ops::{Sub};
 use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
 
 pub fn matrix_constructor(rows: usize, cols: usize) -> Matrix {
     Matrix {
@@ -12,13 +13,39 @@ pub fn matrix_constructor(rows: usize, cols: usize) -> Matrix {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "MatrixData", try_from = "MatrixData")]
 pub struct Matrix {
     pub rows: usize,
     pub cols: usize,
     pub matrix: na::DMatrix<f64>,
 }
 
+// on disk representation of a matrix, values are stored row by row
+#[derive(Serialize, Deserialize)]
+struct MatrixData {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl From<Matrix> for MatrixData {
+    fn from(m: Matrix) -> MatrixData {
+        MatrixData { rows: m.rows, cols: m.cols, data: m.to_array() }
+    }
+}
+
+impl TryFrom<MatrixData> for Matrix {
+    type Error = String;
+
+    fn try_from(m: MatrixData) -> Result<Matrix, String> {
+        if m.data.len() != m.rows * m.cols {
+            return Err(format!("matrix of {}x{} has {} values", m.rows, m.cols, m.data.len()));
+        }
+        Ok(Matrix::new_from_matrix(na::DMatrix::from_row_slice(m.rows, m.cols, &m.data)))
+    }
+}
+
 
 impl Default for Matrix {
     fn default() -> Matrix {
@@ -54,6 +81,13 @@ impl Matrix {
         }
     }
 
+    // copies the block both matrices share (from the top left), the rest of self is kept
+    pub fn copy_overlap(&mut self, m: &Matrix) {
+        let rows = self.rows.min(m.rows);
+        let cols = self.cols.min(m.cols);
+        self.matrix.slice_mut((0, 0), (rows, cols)).copy_from(&m.matrix.slice((0, 0), (rows, cols)));
+    }
+
     pub fn check_equal(m1: &na::DMatrix<f64>, m2: &na::DMatrix<f64>) {
         for row in 0..m1.nrows() {
             for col in 0..m1.ncols() {
@@ -125,6 +159,26 @@ impl Matrix {
         // Matrix::check_equal(&self.matrix, &old);
     }
 
+    // adds the column vector `m` to every column
+    pub fn add_column_vector(&mut self, m: &Matrix) {
+        if self.rows != m.rows || m.cols != 1 {
+            panic!("Matrix add column vector: dimensions don't match: {}x{} vs {}x{}",
+                   self.rows,
+                   self.cols,
+                   m.rows,
+                   m.cols);
+        }
+        for mut col in self.matrix.column_iter_mut() {
+            col += &m.matrix;
+        }
+    }
+
+    // sums every row, returns a column vector
+    pub fn sum_columns(m: &Matrix) -> Matrix {
+        let sum = m.matrix.column_sum();
+        Matrix::new_from_matrix(na::DMatrix::from_column_slice(m.rows, 1, sum.as_slice()))
+    }
+
     pub fn subtract(m1: &Matrix, m2: &Matrix) -> Matrix {
         if m1.rows != m2.rows || m1.cols != m2.cols {
             panic!("Matrix subtract: matrices have different dimensions");
@@ -156,7 +210,7 @@ impl Matrix {
         self.matrix = self.matrix.component_mul(&m.matrix);
     }
     pub fn multiply_with_double(&mut self, d: f64) {
-        Matrix::new_from_matrix(self.matrix.clone().scale(d));
+        self.matrix.scale_mut(d);
     }
     pub fn sigmoid(&mut self) {
         for row in 0..self.rows {
@@ -182,6 +236,11 @@ impl Matrix {
         }
         m
     }
+    // every array becomes one column
+    pub fn from_columns(columns: &[&Vec<f64>]) -> Matrix {
+        let rows = columns[0].len();
+        Matrix::new_from_matrix(na::DMatrix::from_fn(rows, columns.len(), |row, col| columns[col][row]))
+    }
     pub fn from_2d_array(arr: Vec<Vec<f64>>) -> Matrix {
         let mut m: Matrix = matrix_constructor(arr.len(), arr[0].len());
         for i in 0..arr.len() {
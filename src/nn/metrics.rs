@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use crate::nn::neural_network::NeuralNetwork;
+
+// index of the highest value, the first one on ties
+pub fn argmax(values: &[f64]) -> usize {
+    let mut index = 0;
+    for (i, value) in values.iter().enumerate() {
+        if *value > values[index] {
+            index = i;
+        }
+    }
+    index
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfusionMatrix {
+    // counts[actual class][predicted class]
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl ConfusionMatrix {
+    pub fn new(classes: usize) -> ConfusionMatrix {
+        ConfusionMatrix { counts: vec![vec![0; classes]; classes] }
+    }
+
+    // the class of an output or target is the index of its highest value
+    pub fn from_outputs(outputs: &[Vec<f64>], targets: &[Vec<f64>]) -> ConfusionMatrix {
+        if outputs.len() != targets.len() {
+            panic!("ConfusionMatrix: {} outputs but {} targets", outputs.len(), targets.len());
+        }
+        let mut confusion = ConfusionMatrix::new(targets.first().map_or(0, |t| t.len()));
+        for (output, target) in outputs.iter().zip(targets) {
+            confusion.add(argmax(target), argmax(output));
+        }
+        confusion
+    }
+
+    pub fn add(&mut self, actual: usize, predicted: usize) {
+        self.counts[actual][predicted] += 1;
+    }
+
+    pub fn classes(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    pub fn correct(&self) -> usize {
+        (0..self.classes()).map(|c| self.counts[c][c]).sum()
+    }
+
+    // samples of the class
+    pub fn support(&self, class: usize) -> usize {
+        self.counts[class].iter().sum()
+    }
+
+    // samples of the class that were predicted as something else
+    pub fn errors(&self, class: usize) -> usize {
+        self.support(class) - self.counts[class][class]
+    }
+
+    pub fn true_positives(&self, class: usize) -> usize {
+        self.counts[class][class]
+    }
+
+    pub fn false_positives(&self, class: usize) -> usize {
+        self.counts.iter().map(|row| row[class]).sum::<usize>() - self.counts[class][class]
+    }
+
+    pub fn false_negatives(&self, class: usize) -> usize {
+        self.errors(class)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ClassMetrics {
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    pub support: usize,
+}
+
+// all values are fractions (0..=1), divisions by zero count as 0
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    pub confusion: ConfusionMatrix,
+    pub accuracy: f64,
+    pub classes: Vec<ClassMetrics>,
+    // unweighted mean over the classes
+    pub macro_precision: f64,
+    pub macro_recall: f64,
+    pub macro_f1: f64,
+    // from the summed up counts of all classes
+    pub micro_precision: f64,
+    pub micro_recall: f64,
+    pub micro_f1: f64,
+}
+
+fn ratio(a: usize, b: usize) -> f64 {
+    if b == 0 { 0.0 } else { a as f64 / b as f64 }
+}
+
+fn f1(precision: f64, recall: f64) -> f64 {
+    if precision + recall == 0.0 { 0.0 } else { 2.0 * precision * recall / (precision + recall) }
+}
+
+impl Metrics {
+    pub fn from_outputs(outputs: &[Vec<f64>], targets: &[Vec<f64>]) -> Metrics {
+        Metrics::from_confusion(ConfusionMatrix::from_outputs(outputs, targets))
+    }
+
+    pub fn from_confusion(confusion: ConfusionMatrix) -> Metrics {
+        let classes: Vec<ClassMetrics> = (0..confusion.classes()).map(|c| {
+            let tp = confusion.true_positives(c);
+            let precision = ratio(tp, tp + confusion.false_positives(c));
+            let recall = ratio(tp, tp + confusion.false_negatives(c));
+            ClassMetrics { precision, recall, f1: f1(precision, recall), support: confusion.support(c) }
+        }).collect();
+        let mean = |f: fn(&ClassMetrics) -> f64| {
+            if classes.is_empty() { 0.0 } else { classes.iter().map(f).sum::<f64>() / classes.len() as f64 }
+        };
+        let (macro_precision, macro_recall, macro_f1) = (mean(|c| c.precision), mean(|c| c.recall), mean(|c| c.f1));
+
+        // every wrong prediction is a false positive of one class and a false negative of another
+        let tp: usize = (0..confusion.classes()).map(|c| confusion.true_positives(c)).sum();
+        let fp: usize = (0..confusion.classes()).map(|c| confusion.false_positives(c)).sum();
+        let fn_: usize = (0..confusion.classes()).map(|c| confusion.false_negatives(c)).sum();
+        let micro_precision = ratio(tp, tp + fp);
+        let micro_recall = ratio(tp, tp + fn_);
+
+        Metrics {
+            accuracy: ratio(confusion.correct(), confusion.total()),
+            classes,
+            macro_precision,
+            macro_recall,
+            macro_f1,
+            micro_precision,
+            micro_recall,
+            micro_f1: f1(micro_precision, micro_recall),
+            confusion,
+        }
+    }
+}
+
+impl NeuralNetwork {
+    pub fn metrics(&self, inputs: &[Vec<f64>], targets: &[Vec<f64>]) -> Metrics {
+        let outputs: Vec<Vec<f64>> = inputs.iter().map(|input| self.predict(input.clone())).collect();
+        Metrics::from_outputs(&outputs, targets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+    }
+
+    // actual 0: 2 right, 1 taken for class 1; actual 1: 1 right, 1 taken for class 2; class 2 never occurs
+    fn metrics() -> Metrics {
+        let one_hot = |c: usize| (0..3).map(|i| if i == c { 1.0 } else { 0.0 }).collect::<Vec<f64>>();
+        let targets: Vec<Vec<f64>> = [0, 0, 0, 1, 1].iter().map(|c| one_hot(*c)).collect();
+        // the first output is a tie, which goes to the first class
+        let outputs = vec![vec![0.4, 0.4, 0.2], vec![0.9, 0.0, 0.1], vec![0.1, 0.8, 0.1], vec![0.2, 0.7, 0.1], vec![0.0, 0.3, 0.7]];
+        Metrics::from_outputs(&outputs, &targets)
+    }
+
+    #[test]
+    fn confusion_matrix_counts() {
+        let confusion = metrics().confusion;
+        assert_eq!(confusion.counts, vec![vec![2, 1, 0], vec![0, 1, 1], vec![0, 0, 0]]);
+        assert_eq!((confusion.total(), confusion.correct()), (5, 3));
+        assert_eq!((confusion.true_positives(1), confusion.false_positives(1), confusion.false_negatives(1)), (1, 1, 1));
+        assert_eq!((confusion.support(2), confusion.false_positives(2)), (0, 1));
+    }
+
+    #[test]
+    fn per_class_macro_and_micro_scores() {
+        let metrics = metrics();
+        assert_close(metrics.accuracy, 0.6);
+        assert_eq!(metrics.classes[0], ClassMetrics { precision: 1.0, recall: 2.0 / 3.0, f1: 0.8, support: 3 });
+        assert_eq!(metrics.classes[1], ClassMetrics { precision: 0.5, recall: 0.5, f1: 0.5, support: 2 });
+        // no samples and no correct predictions, the divisions by zero count as 0
+        assert_eq!(metrics.classes[2], ClassMetrics { precision: 0.0, recall: 0.0, f1: 0.0, support: 0 });
+        assert_close(metrics.macro_precision, 0.5);
+        assert_close(metrics.macro_recall, (2.0 / 3.0 + 0.5) / 3.0);
+        assert_close(metrics.macro_f1, 1.3 / 3.0);
+        // every error is one false positive and one false negative
+        assert_close(metrics.micro_precision, 0.6);
+        assert_close(metrics.micro_recall, 0.6);
+        assert_close(metrics.micro_f1, 0.6);
+    }
+
+    #[test]
+    fn empty_sets_have_zero_scores() {
+        let metrics = Metrics::from_outputs(&[], &[]);
+        assert_eq!((metrics.accuracy, metrics.macro_f1, metrics.micro_f1), (0.0, 0.0, 0.0));
+        assert!(metrics.classes.is_empty());
+    }
+}
@@ -1,4 +1,12 @@
 pub mod neural_network;
+pub mod activation;
+pub mod loss;
+pub mod optimizer;
+pub mod schedule;
+pub mod model;
 pub mod data_set;
+pub mod csv;
+pub mod idx;
+pub mod metrics;
 pub mod matrix;
 //pub mod matrix_new;
\ No newline at end of file
@@ -0,0 +1,345 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::nn::activation::Activation;
+use crate::nn::loss::LossConfig;
+use crate::nn::matrix::Matrix;
+use crate::nn::neural_network::NeuralNetwork;
+use crate::nn::optimizer::OptimizerConfig;
+use crate::nn::schedule::{ScheduleConfig, ScheduleState};
+
+// only changes when old files can't be read anymore, fields that get added later need #[serde(default)]
+pub const MODEL_FORMAT_VERSION: u32 = 1;
+// binary files start with these bytes followed by the format version (u32, little endian)
+const BINARY_MAGIC: &[u8; 4] = b"NNRS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelFormat {
+    // human readable
+    Json,
+    // MessagePack
+    Binary,
+}
+
+#[derive(Debug)]
+pub enum ModelError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Encode(rmp_serde::encode::Error),
+    Decode(rmp_serde::decode::Error),
+    UnsupportedVersion(u32),
+    Invalid(String),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelError::Io(e) => write!(f, "io error: {}", e),
+            ModelError::Json(e) => write!(f, "invalid json model: {}", e),
+            ModelError::Encode(e) => write!(f, "could not encode model: {}", e),
+            ModelError::Decode(e) => write!(f, "invalid binary model: {}", e),
+            ModelError::UnsupportedVersion(v) => write!(f, "unsupported model format version {} (supported: {})", v, MODEL_FORMAT_VERSION),
+            ModelError::Invalid(e) => write!(f, "invalid model: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+impl From<std::io::Error> for ModelError {
+    fn from(e: std::io::Error) -> ModelError {
+        ModelError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ModelError {
+    fn from(e: serde_json::Error) -> ModelError {
+        ModelError::Json(e)
+    }
+}
+
+impl From<rmp_serde::encode::Error> for ModelError {
+    fn from(e: rmp_serde::encode::Error) -> ModelError {
+        ModelError::Encode(e)
+    }
+}
+
+impl From<rmp_serde::decode::Error> for ModelError {
+    fn from(e: rmp_serde::decode::Error) -> ModelError {
+        ModelError::Decode(e)
+    }
+}
+
+// everything that gets written to disk, the optimizer state is not part of it
+#[derive(Serialize, Deserialize)]
+pub struct ModelFile {
+    format_version: u32,
+    layer_sizes: Vec<usize>,
+    weights: Vec<Matrix>,
+    biases: Vec<Matrix>,
+    activations: Vec<Activation>,
+    loss: LossConfig,
+    optimizer: OptimizerConfig,
+    schedule: ScheduleConfig,
+    learning_rate: f64,
+    batch_size: usize,
+    #[serde(default)]
+    shuffle_seed: u64,
+    #[serde(default)]
+    l2: f64,
+    #[serde(default)]
+    epoch: usize,
+    #[serde(default)]
+    step: usize,
+    // what ReduceOnPlateau learned so far, None for schedules without a state
+    #[serde(default)]
+    schedule_state: Option<ScheduleState>,
+}
+
+impl From<NeuralNetwork> for ModelFile {
+    fn from(nn: NeuralNetwork) -> ModelFile {
+        ModelFile {
+            format_version: MODEL_FORMAT_VERSION,
+            loss: nn.loss.config(),
+            optimizer: nn.optimizer.config(),
+            schedule: nn.schedule.config(),
+            schedule_state: nn.schedule.state(),
+            layer_sizes: nn.layer_sizes,
+            weights: nn.weights,
+            biases: nn.biases,
+            activations: nn.activations,
+            learning_rate: nn.learning_rate,
+            batch_size: nn.batch_size,
+            shuffle_seed: nn.shuffle_seed,
+            l2: nn.l2,
+            epoch: nn.epoch,
+            step: nn.step,
+        }
+    }
+}
+
+impl TryFrom<ModelFile> for NeuralNetwork {
+    type Error = ModelError;
+
+    fn try_from(m: ModelFile) -> Result<NeuralNetwork, ModelError> {
+        if m.format_version != MODEL_FORMAT_VERSION {
+            return Err(ModelError::UnsupportedVersion(m.format_version));
+        }
+        m.validate()?;
+        let mut nn = NeuralNetwork::new_with_activations(m.layer_sizes, m.activations, m.learning_rate, 0);
+        nn.weights = m.weights;
+        nn.biases = m.biases;
+        nn.loss = m.loss.build();
+        nn.optimizer = m.optimizer.build();
+        nn.schedule = m.schedule.build();
+        if let Some(state) = m.schedule_state {
+            nn.schedule.set_state(state);
+        }
+        nn.batch_size = m.batch_size;
+        nn.shuffle_seed = m.shuffle_seed;
+        nn.l2 = m.l2;
+        nn.epoch = m.epoch;
+        nn.step = m.step;
+        Ok(nn)
+    }
+}
+
+impl ModelFile {
+    fn validate(&self) -> Result<(), ModelError> {
+        let invalid = |message: String| Err(ModelError::Invalid(message));
+        let layers = self.layer_sizes.len();
+        if layers < 2 {
+            return invalid(format!("expected at least 2 layers, got {}", layers));
+        }
+        if self.layer_sizes.contains(&0) {
+            return invalid(format!("layer sizes must not be 0: {:?}", self.layer_sizes));
+        }
+        if self.weights.len() != layers - 1 || self.biases.len() != layers - 1 || self.activations.len() != layers - 1 {
+            return invalid(format!("expected {} weights, biases and activations, got {}, {} and {}",
+                                   layers - 1, self.weights.len(), self.biases.len(), self.activations.len()));
+        }
+        for i in 0..layers - 1 {
+            let (rows, cols) = (self.layer_sizes[i + 1], self.layer_sizes[i]);
+            if self.weights[i].rows != rows || self.weights[i].cols != cols {
+                return invalid(format!("weights {} should be {}x{}, got {}x{}", i, rows, cols, self.weights[i].rows, self.weights[i].cols));
+            }
+            if self.biases[i].rows != rows || self.biases[i].cols != 1 {
+                return invalid(format!("biases {} should be {}x1, got {}x{}", i, rows, self.biases[i].rows, self.biases[i].cols));
+            }
+            if self.weights[i].matrix.iter().chain(self.biases[i].matrix.iter()).any(|x| !x.is_finite()) {
+                return invalid(format!("layer {} contains values that are not finite", i + 1));
+            }
+        }
+        if self.batch_size == 0 {
+            return invalid("batch size must not be 0".to_string());
+        }
+        if !(self.l2 >= 0.0 && self.l2.is_finite()) {
+            return invalid(format!("l2 must be a non negative number, got {}", self.l2));
+        }
+        Ok(())
+    }
+}
+
+impl NeuralNetwork {
+    pub fn to_json(&self) -> Result<String, ModelError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<NeuralNetwork, ModelError> {
+        // check the version first, older files might not match the current layout at all
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        match value.get("format_version").and_then(|v| v.as_u64()) {
+            Some(version) if version == MODEL_FORMAT_VERSION as u64 => Ok(serde_json::from_value(value)?),
+            Some(version) => Err(ModelError::UnsupportedVersion(version as u32)),
+            None => Err(ModelError::Invalid("missing format_version".to_string())),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ModelError> {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend_from_slice(&MODEL_FORMAT_VERSION.to_le_bytes());
+        bytes.extend(rmp_serde::to_vec(self)?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<NeuralNetwork, ModelError> {
+        if bytes.len() < 8 || &bytes[0..4] != BINARY_MAGIC {
+            return Err(ModelError::Invalid("not a binary model file".to_string()));
+        }
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != MODEL_FORMAT_VERSION {
+            return Err(ModelError::UnsupportedVersion(version));
+        }
+        Ok(rmp_serde::from_slice(&bytes[8..])?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: ModelFormat) -> Result<(), ModelError> {
+        match format {
+            ModelFormat::Json => fs::write(path, self.to_json()?)?,
+            ModelFormat::Binary => fs::write(path, self.to_bytes()?)?,
+        }
+        Ok(())
+    }
+
+    // the format is detected from the content of the file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<NeuralNetwork, ModelError> {
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(BINARY_MAGIC) {
+            NeuralNetwork::from_bytes(&bytes)
+        } else {
+            let json = String::from_utf8(bytes).map_err(|_| ModelError::Invalid("not a json or binary model file".to_string()))?;
+            NeuralNetwork::from_json(&json)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::schedule::ScheduleUnit;
+
+    fn network() -> NeuralNetwork {
+        let mut nn = NeuralNetwork::new_with_activations(vec![3, 4, 2], vec![Activation::Relu, Activation::Softmax], 0.05, 7);
+        nn.loss = LossConfig::SoftmaxCrossEntropy.build();
+        nn.set_optimizer(OptimizerConfig::Adam { beta1: 0.8, beta2: 0.99, epsilon: 1e-7 });
+        nn.set_schedule(ScheduleConfig::StepDecay { every: 3, gamma: 0.5, unit: ScheduleUnit::Epoch });
+        nn.batch_size = 2;
+        nn
+    }
+
+    fn assert_same(a: &NeuralNetwork, b: &NeuralNetwork) {
+        assert_eq!(a.layer_sizes, b.layer_sizes);
+        for (x, y) in a.weights.iter().zip(&b.weights).chain(a.biases.iter().zip(&b.biases)) {
+            assert_eq!(x.matrix, y.matrix);
+        }
+        assert_eq!(a.activations, b.activations);
+        assert_eq!(a.loss.config(), b.loss.config());
+        assert_eq!(a.optimizer.config(), b.optimizer.config());
+        assert_eq!(a.schedule.config(), b.schedule.config());
+        assert_eq!(a.schedule.state(), b.schedule.state());
+        assert_eq!(a.learning_rate, b.learning_rate);
+        assert_eq!(a.batch_size, b.batch_size);
+        assert_eq!(a.predict(vec![0.1, -0.5, 0.9]), b.predict(vec![0.1, -0.5, 0.9]));
+    }
+
+    fn json_with(nn: &NeuralNetwork, key: &str, value: serde_json::Value) -> String {
+        let mut json: serde_json::Value = serde_json::from_str(&nn.to_json().unwrap()).unwrap();
+        json[key] = value;
+        json.to_string()
+    }
+
+    #[test]
+    fn json_and_binary_round_trips() {
+        let nn = network();
+        assert_same(&nn, &NeuralNetwork::from_json(&nn.to_json().unwrap()).unwrap());
+        assert_same(&nn, &NeuralNetwork::from_bytes(&nn.to_bytes().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn reduce_on_plateau_continues_where_it_stopped() {
+        let mut nn = network();
+        nn.set_schedule(ScheduleConfig::LinearWarmup {
+            warmup: 1,
+            unit: ScheduleUnit::Epoch,
+            then: Box::new(ScheduleConfig::ReduceOnPlateau { factor: 0.5, patience: 0, threshold: 0.0, min_learning_rate: 0.0 }),
+        });
+        // nothing observed yet, the best metric is still infinite
+        assert_same(&nn, &NeuralNetwork::from_json(&nn.to_json().unwrap()).unwrap());
+        for metric in [1.0, 2.0, 0.5, 3.0] {
+            nn.schedule.observe(metric);
+        }
+        assert_eq!(nn.schedule.state(), Some(ScheduleState { best: Some(0.5), bad_epochs: 0, scale: 0.25 }));
+        let mut loaded = NeuralNetwork::from_json(&nn.to_json().unwrap()).unwrap();
+        assert_same(&nn, &loaded);
+        assert_same(&nn, &NeuralNetwork::from_bytes(&nn.to_bytes().unwrap()).unwrap());
+        assert_eq!(loaded.schedule.learning_rate(1.0, 5, 0), 0.25);
+        // files without a state start the schedule over
+        let loaded = NeuralNetwork::from_json(&json_with(&nn, "schedule_state", serde_json::Value::Null)).unwrap();
+        assert_eq!(loaded.schedule.state(), Some(ScheduleState { best: None, bad_epochs: 0, scale: 1.0 }));
+    }
+
+    #[test]
+    fn load_detects_the_format() {
+        let dir = std::env::temp_dir().join(format!("nn_rust_model_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let nn = network();
+        for (name, format) in [("model.json", ModelFormat::Json), ("model.bin", ModelFormat::Binary)] {
+            nn.save(dir.join(name), format).unwrap();
+            assert_same(&nn, &NeuralNetwork::load(dir.join(name)).unwrap());
+        }
+        fs::write(dir.join("garbage"), [0xFF, 0xFE, 0x00]).unwrap();
+        assert!(matches!(NeuralNetwork::load(dir.join("garbage")), Err(ModelError::Invalid(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn headers_and_versions_are_checked() {
+        let nn = network();
+        let mut bytes = nn.to_bytes().unwrap();
+        bytes[0] = b'X';
+        assert!(matches!(NeuralNetwork::from_bytes(&bytes), Err(ModelError::Invalid(_))));
+        assert!(matches!(NeuralNetwork::from_bytes(b"NNRS"), Err(ModelError::Invalid(_))));
+        let mut bytes = nn.to_bytes().unwrap();
+        bytes[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(NeuralNetwork::from_bytes(&bytes), Err(ModelError::UnsupportedVersion(2))));
+
+        assert!(matches!(NeuralNetwork::from_json(&json_with(&nn, "format_version", 2.into())), Err(ModelError::UnsupportedVersion(2))));
+        let mut json: serde_json::Value = serde_json::from_str(&nn.to_json().unwrap()).unwrap();
+        json.as_object_mut().unwrap().remove("format_version");
+        assert!(matches!(NeuralNetwork::from_json(&json.to_string()), Err(ModelError::Invalid(_))));
+    }
+
+    #[test]
+    fn layer_shapes_are_validated() {
+        let nn = network();
+        let error = NeuralNetwork::from_json(&json_with(&nn, "layer_sizes", serde_json::json!([3, 5, 2]))).unwrap_err();
+        assert!(error.to_string().contains("weights 0 should be 5x3, got 4x3"), "{}", error);
+        let error = NeuralNetwork::from_json(&json_with(&nn, "layer_sizes", serde_json::json!([3]))).unwrap_err();
+        assert!(error.to_string().contains("expected at least 2 layers"), "{}", error);
+        let error = NeuralNetwork::from_json(&json_with(&nn, "activations", serde_json::json!(["Relu"]))).unwrap_err();
+        assert!(error.to_string().contains("expected 2 weights, biases and activations, got 2, 2 and 1"), "{}", error);
+        let error = NeuralNetwork::from_json(&json_with(&nn, "batch_size", 0.into())).unwrap_err();
+        assert!(error.to_string().contains("batch size must not be 0"), "{}", error);
+    }
+}
@@ -1,16 +1,49 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use crate::nn::activation::Activation;
+use crate::nn::loss::{Loss, MeanSquaredError};
 use crate::nn::matrix::Matrix;
+use crate::nn::model::ModelFile;
+use crate::nn::optimizer::{Optimizer, OptimizerConfig};
+use crate::nn::schedule::{LearningRateSchedule, ScheduleConfig};
 
-#[derive(Debug, Clone)]
+// inputs and targets of a validation set
+pub type ValidationSet<'a> = (&'a [Vec<f64>], &'a [Vec<f64>]);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "ModelFile", try_from = "ModelFile")]
 pub struct NeuralNetwork {
     pub layer_sizes: Vec<usize>,
     pub weights: Vec<Matrix>,
     pub biases: Vec<Matrix>,
+    pub activations: Vec<Activation>,
+    pub loss: Box<dyn Loss>,
+    pub optimizer: Box<dyn Optimizer>,
+    pub schedule: Box<dyn LearningRateSchedule>,
     pub learning_rate: f64,
+    pub batch_size: usize,
+    // strength of the L2 penalty on the weights (not the biases), 0 disables it
+    pub l2: f64,
+    // seeds the shuffle of the samples in fit, advances after every call
+    pub shuffle_seed: u64,
+    // epochs and steps trained so far, schedules count from them across calls of fit
+    pub epoch: usize,
+    pub step: usize,
 }
 
 impl NeuralNetwork {
     pub fn new(layer_sizes: Vec<usize>, learning_rate: f64, seed: i32) -> NeuralNetwork {
+        let activations = vec![Activation::Sigmoid; layer_sizes.len() - 1];
+        NeuralNetwork::new_with_activations(layer_sizes, activations, learning_rate, seed)
+    }
+
+    // one activation per layer after the input layer
+    pub fn new_with_activations(layer_sizes: Vec<usize>, activations: Vec<Activation>, learning_rate: f64, seed: i32) -> NeuralNetwork {
+        if activations.len() != layer_sizes.len() - 1 {
+            panic!("NeuralNetwork: expected {} activations, got {}", layer_sizes.len() - 1, activations.len());
+        }
         let mut weights: Vec<Matrix> = Vec::new();
         let mut biases: Vec<Matrix> = Vec::new();
         for i in 0..layer_sizes.len() - 1 {
@@ -23,78 +56,184 @@ impl NeuralNetwork {
             layer_sizes,
             weights,
             biases,
+            activations,
+            loss: Box::new(MeanSquaredError),
+            optimizer: OptimizerConfig::default().build(),
+            schedule: ScheduleConfig::default().build(),
             learning_rate,
+            batch_size: 1,
+            l2: 0.0,
+            shuffle_seed: seed as u64,
+            epoch: 0,
+            step: 0,
         }
     }
 
-    pub fn predict(&self, input: Vec<f64>) -> Vec<f64> {
-        let mut layers: Vec<Matrix> = Vec::new();
-        layers.push(Matrix::from_array(input));
+    // returns the weighted sums and the activated values of every layer,
+    // index 0 holds the input for both, every column of `input` is one sample
+    fn forward(&self, input: Matrix) -> (Vec<Matrix>, Vec<Matrix>) {
+        let mut sums: Vec<Matrix> = vec![input.clone()];
+        let mut layers: Vec<Matrix> = vec![input];
 
         for i in 0..self.layer_sizes.len() - 1 {
-            let mut layer: Matrix = Matrix::multiply(&self.weights[i], &layers[i]);
-            layer.add_matrix(&self.biases[i]);
-            layer.sigmoid();
+            let mut sum: Matrix = Matrix::multiply(&self.weights[i], &layers[i]);
+            sum.add_column_vector(&self.biases[i]);
+            let mut layer = sum.clone();
+            self.activations[i].apply(&mut layer);
+            sums.push(sum);
             layers.push(layer);
         }
+        (sums, layers)
+    }
+
+    pub fn predict(&self, input: Vec<f64>) -> Vec<f64> {
+        let (_, layers) = self.forward(Matrix::from_array(input));
         layers[layers.len() - 1].to_array()
     }
 
     pub fn train(&mut self, input: Vec<f64>, target_v: Vec<f64>) -> Vec<f64> {
-        let mut layers: Vec<Matrix> = Vec::new();
-        layers.push(Matrix::from_array(input));
+        let (output, _) = self.train_matrix(Matrix::from_array(input), &Matrix::from_array(target_v), self.learning_rate);
+        output.to_array()
+    }
 
-        for i in 0..self.layer_sizes.len() - 1 {
-            let mut layer: Matrix = Matrix::multiply(&self.weights[i], &layers[i]);
-            layer.add_matrix(&self.biases[i]);
-            layer.sigmoid();
-            layers.push(layer);
-        }
+    // one step of gradient descent over a batch (one sample per column),
+    // returns the output of the network and the average loss of the batch
+    fn train_matrix(&mut self, input: Matrix, target: &Matrix, learning_rate: f64) -> (Matrix, f64) {
+        let (sums, mut layers) = self.forward(input);
+        let last = layers.len() - 1;
+        let activation = self.activations[last - 1];
+        let loss = self.loss.output_loss(activation, &sums[last], &layers[last], target);
 
-        let target = Matrix::from_array(target_v);
-        let mut error = Matrix::subtract(&target, &layers[layers.len() - 1]);
-        let mut transposed: Matrix;
-        self.correct_error(layers.len() - 1, &layers, &error);
-        for i in (1..self.layer_sizes.len() - 1).rev() {
-            transposed = Matrix::transpose(&self.weights[i]);
-            error = Matrix::multiply(&transposed, &error);
-            self.correct_error(i, &layers, &error);
+        let mut weight_gradients: Vec<Matrix> = Vec::new();
+        let mut bias_gradients: Vec<Matrix> = Vec::new();
+        let mut h_gradient = self.loss.output_gradient(activation, &sums[last], &layers[last], target);
+        for i in (1..=last).rev() {
+            let (weight_gradient, bias_gradient) = NeuralNetwork::gradients(i, &layers, &h_gradient);
+            weight_gradients.push(weight_gradient);
+            bias_gradients.push(bias_gradient);
+            if i > 1 {
+                let error = Matrix::multiply(&Matrix::transpose(&self.weights[i - 1]), &h_gradient);
+                h_gradient = self.activations[i - 2].backprop(&sums[i - 1], &layers[i - 1], &error);
+            }
         }
-        layers[layers.len() - 1].to_array()
+        weight_gradients.reverse();
+        bias_gradients.reverse();
+        // gradient of 0.5 * l2 * sum(w^2), the returned loss doesn't include the penalty
+        if self.l2 > 0.0 {
+            for (gradient, weight) in weight_gradients.iter_mut().zip(&self.weights) {
+                gradient.matrix += &weight.matrix * self.l2;
+            }
+        }
+        self.optimizer.step(&mut self.weights, &mut self.biases, &weight_gradients, &bias_gradients, learning_rate);
+        (layers.swap_remove(last), loss)
     }
 
-    fn correct_error(&mut self, i: usize, layers: &Vec<Matrix>, error: &Matrix) {
-        let mut h_gradient: Matrix = Matrix::dsigmoid(&layers[i]);
-        h_gradient.multiply_with_matrix(error);
-        h_gradient.multiply_with_double(self.learning_rate);
+    // gradients of the weights and biases feeding into layer i, averaged over the batch
+    fn gradients(i: usize, layers: &[Matrix], h_gradient: &Matrix) -> (Matrix, Matrix) {
+        let scaler = 1.0 / h_gradient.cols as f64;
+
+        let mut weight_gradient = Matrix::multiply(h_gradient, &Matrix::transpose(&layers[i - 1]));
+        weight_gradient.multiply_with_double(scaler);
+        let mut bias_gradient = Matrix::sum_columns(h_gradient);
+        bias_gradient.multiply_with_double(scaler);
+        (weight_gradient, bias_gradient)
+    }
 
-        let wih_delta = Matrix::multiply(&h_gradient, &Matrix::transpose(&layers[i - 1]));
-        self.weights[i - 1].add_matrix(&wih_delta);
-        self.biases[i - 1].add_matrix(&h_gradient);
+    pub fn set_optimizer(&mut self, config: OptimizerConfig) {
+        self.optimizer = config.build();
     }
 
-    pub fn fit(&mut self, inputs: &Vec<Vec<f64>>, targets: &Vec<Vec<f64>>, epochs: i32) {
-        for _e in 0..epochs {
+    pub fn set_schedule(&mut self, config: ScheduleConfig) {
+        self.schedule = config.build();
+    }
+
+    // average loss over a whole data set, without training
+    pub fn evaluate(&self, inputs: &[Vec<f64>], targets: &[Vec<f64>]) -> f64 {
+        if inputs.is_empty() {
+            return 0.0;
+        }
+        let samples: Vec<usize> = (0..inputs.len()).collect();
+        let mut loss = 0.0;
+        for batch in samples.chunks(self.batch_size.max(1)) {
+            let input = Matrix::from_columns(&batch.iter().map(|i| &inputs[*i]).collect::<Vec<_>>());
+            let target = Matrix::from_columns(&batch.iter().map(|i| &targets[*i]).collect::<Vec<_>>());
+            let (sums, layers) = self.forward(input);
+            let last = layers.len() - 1;
+            loss += self.loss.output_loss(self.activations[last - 1], &sums[last], &layers[last], &target) * batch.len() as f64;
+        }
+        loss / inputs.len() as f64
+    }
+
+    // returns the average loss of every epoch
+    pub fn fit(&mut self, inputs: &[Vec<f64>], targets: &[Vec<f64>], epochs: i32) -> Vec<f64> {
+        self.fit_with_validation(inputs, targets, None, epochs)
+    }
+
+    // like fit, the loss on the validation set (inputs, targets) is what the schedule observes
+    pub fn fit_with_validation(&mut self, inputs: &[Vec<f64>], targets: &[Vec<f64>], validation: Option<ValidationSet>, epochs: i32) -> Vec<f64> {
+        let mut losses: Vec<f64> = Vec::new();
+        // nothing to train, the counters and the shuffle seed stay as they are
+        if epochs <= 0 || inputs.is_empty() {
+            return losses;
+        }
+        let mut rng = StdRng::seed_from_u64(self.shuffle_seed);
+        for _ in 0..epochs {
             let mut samples: Vec<usize> = (0..inputs.len()).collect();
-            samples.shuffle(&mut rand::thread_rng());
-
-            let mut _errors_this_epoch = 0;
-            for i in samples {
-                // println!("input: {:?}", inputs[i].clone());
-                // println!("target: {:?}", targets[i].clone());
-                let _result = self.train(inputs[i].clone(), targets[i].clone());
-                // check result
-                // let bit_result: Vec<i32> = result.iter().map(|x| if *x > 0.5 { 1 } else { 0 }).collect();
-                // for j in 0..bit_result.len() {
-                //     if bit_result[j] != targets[i][j] as i32 {
-                //         _errors_this_epoch += 1;
-                //         break;
-                //     }
-                // }
+            samples.shuffle(&mut rng);
+
+            let mut loss_this_epoch = 0.0;
+            for batch in samples.chunks(self.batch_size.max(1)) {
+                let input = Matrix::from_columns(&batch.iter().map(|i| &inputs[*i]).collect::<Vec<_>>());
+                let target = Matrix::from_columns(&batch.iter().map(|i| &targets[*i]).collect::<Vec<_>>());
+                let learning_rate = self.schedule.learning_rate(self.learning_rate, self.epoch, self.step);
+                loss_this_epoch += self.train_matrix(input, &target, learning_rate).1 * batch.len() as f64;
+                self.step += 1;
             }
-            // if e % 10000 == 0 {
-            //     println!("Epoch: {}, Errors: {}", e, errors_this_epoch);
-            // }
+            loss_this_epoch /= inputs.len() as f64;
+            match validation {
+                Some((validation_inputs, validation_targets)) => self.schedule.observe(self.evaluate(validation_inputs, validation_targets)),
+                None => self.schedule.observe(loss_this_epoch),
+            }
+            losses.push(loss_this_epoch);
+            self.epoch += 1;
         }
+        self.shuffle_seed = rng.gen();
+        losses
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nn::schedule::ScheduleUnit;
+
+    #[test]
+    fn schedules_count_epochs_and_steps_across_calls_of_fit() {
+        let inputs = vec![vec![0.0, 1.0], vec![1.0, 0.0], vec![1.0, 1.0]];
+        let targets = vec![vec![1.0], vec![0.0], vec![1.0]];
+        let mut nn = NeuralNetwork::new(vec![2, 3, 1], 0.1, 1);
+        nn.batch_size = 2;
+        nn.set_schedule(ScheduleConfig::StepDecay { every: 1, gamma: 0.5, unit: ScheduleUnit::Epoch });
+        nn.fit(&inputs, &targets, 1);
+        nn.fit(&inputs, &targets, 1);
+        assert_eq!((nn.epoch, nn.step), (2, 4));
+        assert_eq!(nn.schedule.learning_rate(nn.learning_rate, nn.epoch, nn.step), 0.025);
+
+        let loaded = NeuralNetwork::from_json(&nn.to_json().unwrap()).unwrap();
+        assert_eq!((loaded.epoch, loaded.step), (2, 4));
+    }
+
+    #[test]
+    fn nothing_to_train_on_leaves_the_network_alone() {
+        let inputs = vec![vec![0.0, 1.0]];
+        let targets = vec![vec![1.0]];
+        let mut nn = NeuralNetwork::new(vec![2, 3, 1], 0.1, 1);
+        let untouched = nn.to_json().unwrap();
+        assert!(nn.fit(&inputs, &targets, 0).is_empty());
+        assert!(nn.fit(&inputs, &targets, -1).is_empty());
+        assert!(nn.fit(&[], &[], 3).is_empty());
+        assert_eq!(nn.to_json().unwrap(), untouched);
+        assert_eq!(nn.evaluate(&[], &[]), 0.0);
+        assert_eq!(nn.fit(&inputs, &targets, 2).len(), 2);
+    }
+}
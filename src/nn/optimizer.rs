@@ -0,0 +1,331 @@
+extern crate nalgebra as na;
+
+use std::fmt::Debug;
+use serde::{Deserialize, Serialize};
+use crate::nn::matrix::Matrix;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OptimizerConfig {
+    Sgd { momentum: f64, nesterov: bool },
+    RmsProp { decay: f64, epsilon: f64 },
+    Adam { beta1: f64, beta2: f64, epsilon: f64 },
+    AdamW { beta1: f64, beta2: f64, epsilon: f64, weight_decay: f64 },
+}
+
+impl Default for OptimizerConfig {
+    fn default() -> OptimizerConfig {
+        OptimizerConfig::Sgd { momentum: 0.0, nesterov: false }
+    }
+}
+
+impl OptimizerConfig {
+    pub fn build(&self) -> Box<dyn Optimizer> {
+        match *self {
+            OptimizerConfig::Sgd { momentum, nesterov } => Box::new(Sgd::new(momentum, nesterov)),
+            OptimizerConfig::RmsProp { decay, epsilon } => Box::new(RmsProp::new(decay, epsilon)),
+            OptimizerConfig::Adam { beta1, beta2, epsilon } => Box::new(Adam::new(beta1, beta2, epsilon, 0.0)),
+            OptimizerConfig::AdamW { beta1, beta2, epsilon, weight_decay } => Box::new(Adam::new(beta1, beta2, epsilon, weight_decay)),
+        }
+    }
+}
+
+// an optimizer keeps its own state for every matrix in the weights and biases of a network,
+// the state is created on the first step and recreated when the shapes of the network change
+pub trait Optimizer: Debug + Send + Sync {
+    // the gradients point uphill, the parameters get moved against them
+    fn step(&mut self, weights: &mut [Matrix], biases: &mut [Matrix], weight_gradients: &[Matrix], bias_gradients: &[Matrix], learning_rate: f64);
+
+    fn config(&self) -> OptimizerConfig;
+
+    fn box_clone(&self) -> Box<dyn Optimizer>;
+}
+
+impl Clone for Box<dyn Optimizer> {
+    fn clone(&self) -> Box<dyn Optimizer> {
+        self.box_clone()
+    }
+}
+
+// one zero matrix per parameter, keeps the existing state if the shapes still match
+fn init_state(state: &mut Vec<na::DMatrix<f64>>, parameters: &[Matrix]) {
+    let matches = state.len() == parameters.len()
+        && state.iter().zip(parameters).all(|(s, p)| s.shape() == p.matrix.shape());
+    if !matches {
+        *state = parameters.iter().map(|p| na::DMatrix::zeros(p.rows, p.cols)).collect();
+    }
+}
+
+// plain gradient descent, with optional (nesterov) momentum
+#[derive(Debug, Clone)]
+pub struct Sgd {
+    pub momentum: f64,
+    pub nesterov: bool,
+    weight_velocity: Vec<na::DMatrix<f64>>,
+    bias_velocity: Vec<na::DMatrix<f64>>,
+}
+
+impl Sgd {
+    pub fn new(momentum: f64, nesterov: bool) -> Sgd {
+        Sgd { momentum, nesterov, weight_velocity: Vec::new(), bias_velocity: Vec::new() }
+    }
+
+    fn update(&self, parameter: &mut Matrix, gradient: &Matrix, velocity: &mut na::DMatrix<f64>, learning_rate: f64) {
+        if self.momentum == 0.0 {
+            parameter.matrix -= &gradient.matrix * learning_rate;
+            return;
+        }
+        *velocity *= self.momentum;
+        *velocity += &gradient.matrix;
+        if self.nesterov {
+            parameter.matrix -= (&gradient.matrix + &*velocity * self.momentum) * learning_rate;
+        } else {
+            parameter.matrix -= &*velocity * learning_rate;
+        }
+    }
+}
+
+impl Optimizer for Sgd {
+    fn step(&mut self, weights: &mut [Matrix], biases: &mut [Matrix], weight_gradients: &[Matrix], bias_gradients: &[Matrix], learning_rate: f64) {
+        init_state(&mut self.weight_velocity, weights);
+        init_state(&mut self.bias_velocity, biases);
+        let mut weight_velocity = std::mem::take(&mut self.weight_velocity);
+        let mut bias_velocity = std::mem::take(&mut self.bias_velocity);
+        for i in 0..weights.len() {
+            self.update(&mut weights[i], &weight_gradients[i], &mut weight_velocity[i], learning_rate);
+            self.update(&mut biases[i], &bias_gradients[i], &mut bias_velocity[i], learning_rate);
+        }
+        self.weight_velocity = weight_velocity;
+        self.bias_velocity = bias_velocity;
+    }
+
+    fn config(&self) -> OptimizerConfig {
+        OptimizerConfig::Sgd { momentum: self.momentum, nesterov: self.nesterov }
+    }
+
+    fn box_clone(&self) -> Box<dyn Optimizer> {
+        Box::new(self.clone())
+    }
+}
+
+// divides the gradient by a moving average of its magnitude
+#[derive(Debug, Clone)]
+pub struct RmsProp {
+    pub decay: f64,
+    pub epsilon: f64,
+    weight_cache: Vec<na::DMatrix<f64>>,
+    bias_cache: Vec<na::DMatrix<f64>>,
+}
+
+impl RmsProp {
+    pub fn new(decay: f64, epsilon: f64) -> RmsProp {
+        RmsProp { decay, epsilon, weight_cache: Vec::new(), bias_cache: Vec::new() }
+    }
+
+    fn update(&self, parameter: &mut Matrix, gradient: &Matrix, cache: &mut na::DMatrix<f64>, learning_rate: f64) {
+        *cache *= self.decay;
+        *cache += gradient.matrix.map(|g| g * g) * (1.0 - self.decay);
+        parameter.matrix -= gradient.matrix.zip_map(cache, |g, s| learning_rate * g / (s.sqrt() + self.epsilon));
+    }
+}
+
+impl Optimizer for RmsProp {
+    fn step(&mut self, weights: &mut [Matrix], biases: &mut [Matrix], weight_gradients: &[Matrix], bias_gradients: &[Matrix], learning_rate: f64) {
+        init_state(&mut self.weight_cache, weights);
+        init_state(&mut self.bias_cache, biases);
+        let mut weight_cache = std::mem::take(&mut self.weight_cache);
+        let mut bias_cache = std::mem::take(&mut self.bias_cache);
+        for i in 0..weights.len() {
+            self.update(&mut weights[i], &weight_gradients[i], &mut weight_cache[i], learning_rate);
+            self.update(&mut biases[i], &bias_gradients[i], &mut bias_cache[i], learning_rate);
+        }
+        self.weight_cache = weight_cache;
+        self.bias_cache = bias_cache;
+    }
+
+    fn config(&self) -> OptimizerConfig {
+        OptimizerConfig::RmsProp { decay: self.decay, epsilon: self.epsilon }
+    }
+
+    fn box_clone(&self) -> Box<dyn Optimizer> {
+        Box::new(self.clone())
+    }
+}
+
+// adam, with a weight decay above zero it becomes adamw (decay decoupled from the gradient,
+// only applied to the weights)
+#[derive(Debug, Clone)]
+pub struct Adam {
+    pub beta1: f64,
+    pub beta2: f64,
+    pub epsilon: f64,
+    pub weight_decay: f64,
+    steps: i32,
+    weight_moments: Vec<(na::DMatrix<f64>, na::DMatrix<f64>)>,
+    bias_moments: Vec<(na::DMatrix<f64>, na::DMatrix<f64>)>,
+}
+
+impl Adam {
+    pub fn new(beta1: f64, beta2: f64, epsilon: f64, weight_decay: f64) -> Adam {
+        Adam { beta1, beta2, epsilon, weight_decay, steps: 0, weight_moments: Vec::new(), bias_moments: Vec::new() }
+    }
+
+    fn init_moments(moments: &mut Vec<(na::DMatrix<f64>, na::DMatrix<f64>)>, parameters: &[Matrix]) -> bool {
+        let matches = moments.len() == parameters.len()
+            && moments.iter().zip(parameters).all(|(m, p)| m.0.shape() == p.matrix.shape());
+        if !matches {
+            *moments = parameters.iter().map(|p| (na::DMatrix::zeros(p.rows, p.cols), na::DMatrix::zeros(p.rows, p.cols))).collect();
+        }
+        matches
+    }
+
+    fn update(&self, parameter: &mut Matrix, gradient: &Matrix, moments: &mut (na::DMatrix<f64>, na::DMatrix<f64>), learning_rate: f64, weight_decay: f64) {
+        let (m, v) = moments;
+        *m *= self.beta1;
+        *m += &gradient.matrix * (1.0 - self.beta1);
+        *v *= self.beta2;
+        *v += gradient.matrix.map(|g| g * g) * (1.0 - self.beta2);
+
+        let m_correction = 1.0 - self.beta1.powi(self.steps);
+        let v_correction = 1.0 - self.beta2.powi(self.steps);
+        if weight_decay != 0.0 {
+            parameter.matrix *= 1.0 - learning_rate * weight_decay;
+        }
+        parameter.matrix -= m.zip_map(v, |m, v| learning_rate * (m / m_correction) / ((v / v_correction).sqrt() + self.epsilon));
+    }
+}
+
+impl Optimizer for Adam {
+    fn step(&mut self, weights: &mut [Matrix], biases: &mut [Matrix], weight_gradients: &[Matrix], bias_gradients: &[Matrix], learning_rate: f64) {
+        let weights_match = Adam::init_moments(&mut self.weight_moments, weights);
+        let biases_match = Adam::init_moments(&mut self.bias_moments, biases);
+        if !weights_match || !biases_match {
+            self.steps = 0;
+        }
+        self.steps += 1;
+        let mut weight_moments = std::mem::take(&mut self.weight_moments);
+        let mut bias_moments = std::mem::take(&mut self.bias_moments);
+        for i in 0..weights.len() {
+            self.update(&mut weights[i], &weight_gradients[i], &mut weight_moments[i], learning_rate, self.weight_decay);
+            self.update(&mut biases[i], &bias_gradients[i], &mut bias_moments[i], learning_rate, 0.0);
+        }
+        self.weight_moments = weight_moments;
+        self.bias_moments = bias_moments;
+    }
+
+    fn config(&self) -> OptimizerConfig {
+        if self.weight_decay != 0.0 {
+            OptimizerConfig::AdamW { beta1: self.beta1, beta2: self.beta2, epsilon: self.epsilon, weight_decay: self.weight_decay }
+        } else {
+            OptimizerConfig::Adam { beta1: self.beta1, beta2: self.beta2, epsilon: self.epsilon }
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn Optimizer> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEARNING_RATE: f64 = 0.1;
+
+    fn row(values: &[f64]) -> Matrix {
+        Matrix::from_2d_array(vec![values.to_vec()])
+    }
+
+    // one step on a single weight matrix and bias
+    fn step(optimizer: &mut dyn Optimizer, weights: &mut Matrix, biases: &mut Matrix, weight_gradient: &[f64], bias_gradient: &[f64]) {
+        let mut w = [weights.clone()];
+        let mut b = [biases.clone()];
+        optimizer.step(&mut w, &mut b, &[row(weight_gradient)], &[row(bias_gradient)], LEARNING_RATE);
+        *weights = w[0].clone();
+        *biases = b[0].clone();
+    }
+
+    fn assert_close(actual: &Matrix, expected: &[f64]) {
+        for (a, e) in actual.matrix.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "got {:?}, expected {:?}", actual.matrix.as_slice(), expected);
+        }
+    }
+
+    #[test]
+    fn sgd_moves_against_the_gradient() {
+        let mut weights = row(&[1.0, -2.0]);
+        let mut biases = row(&[0.5]);
+        step(&mut Sgd::new(0.0, false), &mut weights, &mut biases, &[2.0, -1.0], &[4.0]);
+        assert_close(&weights, &[0.8, -1.9]);
+        assert_close(&biases, &[0.1]);
+    }
+
+    #[test]
+    fn sgd_momentum_accumulates_the_velocity() {
+        let mut weights = row(&[1.0, 0.0]);
+        let mut biases = row(&[0.0]);
+        let mut sgd = Sgd::new(0.9, false);
+        step(&mut sgd, &mut weights, &mut biases, &[1.0, -1.0], &[0.0]);
+        assert_close(&weights, &[0.9, 0.1]);
+        // velocity 0.9 * 1 + 1
+        step(&mut sgd, &mut weights, &mut biases, &[1.0, -1.0], &[0.0]);
+        assert_close(&weights, &[0.71, 0.29]);
+
+        // nesterov looks one step ahead: gradient + momentum * velocity
+        let mut weights = row(&[1.0, 0.0]);
+        step(&mut Sgd::new(0.9, true), &mut weights, &mut biases, &[1.0, -1.0], &[0.0]);
+        assert_close(&weights, &[0.81, 0.19]);
+    }
+
+    #[test]
+    fn rms_prop_scales_by_the_gradient_magnitude() {
+        let mut weights = row(&[1.0, 0.0]);
+        let mut biases = row(&[0.0]);
+        step(&mut RmsProp::new(0.75, 0.0), &mut weights, &mut biases, &[2.0, -8.0], &[0.0]);
+        // the cache is 0.25 * g^2, so every value moves by learning_rate / sqrt(0.25)
+        assert_close(&weights, &[0.8, 0.2]);
+    }
+
+    #[test]
+    fn adam_first_step_is_the_learning_rate() {
+        let mut weights = row(&[1.0, 0.0]);
+        let mut biases = row(&[0.0]);
+        // the bias correction makes the first step learning_rate * sign(gradient)
+        step(&mut Adam::new(0.9, 0.999, 0.0, 0.0), &mut weights, &mut biases, &[3.0, -0.01], &[5.0]);
+        assert_close(&weights, &[0.9, 0.1]);
+        assert_close(&biases, &[-0.1]);
+    }
+
+    #[test]
+    fn adam_w_decays_only_the_weights() {
+        let mut weights = row(&[1.0, -2.0]);
+        let mut biases = row(&[1.0]);
+        step(&mut Adam::new(0.9, 0.999, 1e-8, 0.5), &mut weights, &mut biases, &[0.0, 0.0], &[0.0]);
+        assert_close(&weights, &[0.95, -1.9]);
+        assert_close(&biases, &[1.0]);
+    }
+
+    #[test]
+    fn every_optimizer_minimizes_a_quadratic() {
+        let configs = [
+            OptimizerConfig::default(),
+            OptimizerConfig::Sgd { momentum: 0.5, nesterov: true },
+            OptimizerConfig::RmsProp { decay: 0.9, epsilon: 1e-8 },
+            OptimizerConfig::Adam { beta1: 0.9, beta2: 0.999, epsilon: 1e-8 },
+            OptimizerConfig::AdamW { beta1: 0.9, beta2: 0.999, epsilon: 1e-8, weight_decay: 1e-4 },
+        ];
+        for config in configs {
+            let mut optimizer = config.build();
+            assert_eq!(optimizer.config(), config);
+            // 0.5 * (w - 3)^2 + 0.5 * (b + 1)^2
+            let mut weights = row(&[0.0]);
+            let mut biases = row(&[0.0]);
+            for _ in 0..500 {
+                let weight_gradient = weights.matrix[(0, 0)] - 3.0;
+                let bias_gradient = biases.matrix[(0, 0)] + 1.0;
+                step(optimizer.as_mut(), &mut weights, &mut biases, &[weight_gradient], &[bias_gradient]);
+            }
+            assert!((weights.matrix[(0, 0)] - 3.0).abs() < 0.05, "{:?} ended at {}", config, weights.matrix[(0, 0)]);
+            assert!((biases.matrix[(0, 0)] + 1.0).abs() < 0.05, "{:?} ended at {}", config, biases.matrix[(0, 0)]);
+        }
+    }
+}
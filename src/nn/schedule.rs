@@ -0,0 +1,343 @@
+use std::f64::consts::PI;
+use std::fmt::Debug;
+use serde::{Deserialize, Serialize};
+
+// whether a schedule counts epochs or steps (batches)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScheduleUnit {
+    Epoch,
+    Step,
+}
+
+impl ScheduleUnit {
+    fn pick(&self, epoch: usize, step: usize) -> usize {
+        match self {
+            ScheduleUnit::Epoch => epoch,
+            ScheduleUnit::Step => step,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum ScheduleConfig {
+    #[default]
+    Constant,
+    StepDecay { every: usize, gamma: f64, unit: ScheduleUnit },
+    ExponentialDecay { gamma: f64, unit: ScheduleUnit },
+    CosineAnnealing { period: usize, period_multiplier: usize, min_learning_rate: f64, unit: ScheduleUnit },
+    LinearWarmup { warmup: usize, unit: ScheduleUnit, then: Box<ScheduleConfig> },
+    ReduceOnPlateau { factor: f64, patience: usize, threshold: f64, min_learning_rate: f64 },
+}
+
+impl ScheduleConfig {
+    pub fn build(&self) -> Box<dyn LearningRateSchedule> {
+        match self {
+            ScheduleConfig::Constant => Box::new(Constant),
+            ScheduleConfig::StepDecay { every, gamma, unit } => Box::new(StepDecay { every: *every, gamma: *gamma, unit: *unit }),
+            ScheduleConfig::ExponentialDecay { gamma, unit } => Box::new(ExponentialDecay { gamma: *gamma, unit: *unit }),
+            ScheduleConfig::CosineAnnealing { period, period_multiplier, min_learning_rate, unit } => Box::new(CosineAnnealing {
+                period: *period,
+                period_multiplier: *period_multiplier,
+                min_learning_rate: *min_learning_rate,
+                unit: *unit,
+            }),
+            ScheduleConfig::LinearWarmup { warmup, unit, then } => Box::new(LinearWarmup { warmup: *warmup, unit: *unit, then: then.build() }),
+            ScheduleConfig::ReduceOnPlateau { factor, patience, threshold, min_learning_rate } => {
+                Box::new(ReduceOnPlateau::new(*factor, *patience, *threshold, *min_learning_rate))
+            }
+        }
+    }
+}
+
+// what ReduceOnPlateau learned from the observed metrics, saved with the model so a loaded
+// network continues where it stopped
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleState {
+    // None until the first metric was observed
+    pub best: Option<f64>,
+    pub bad_epochs: usize,
+    pub scale: f64,
+}
+
+// consulted before every step of NeuralNetwork::fit, `epoch` and `step` count everything the network
+// was trained for, so training one epoch per call of fit moves along the schedule as well
+pub trait LearningRateSchedule: Debug + Send + Sync {
+    fn learning_rate(&mut self, base: f64, epoch: usize, step: usize) -> f64;
+
+    // called after every epoch with the validation loss (or the training loss without validation data)
+    fn observe(&mut self, _metric: f64) {}
+
+    fn config(&self) -> ScheduleConfig;
+
+    // only schedules that observe metrics have a state
+    fn state(&self) -> Option<ScheduleState> {
+        None
+    }
+
+    fn set_state(&mut self, _state: ScheduleState) {}
+
+    fn box_clone(&self) -> Box<dyn LearningRateSchedule>;
+}
+
+impl Clone for Box<dyn LearningRateSchedule> {
+    fn clone(&self) -> Box<dyn LearningRateSchedule> {
+        self.box_clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Constant;
+
+impl LearningRateSchedule for Constant {
+    fn learning_rate(&mut self, base: f64, _epoch: usize, _step: usize) -> f64 {
+        base
+    }
+
+    fn config(&self) -> ScheduleConfig {
+        ScheduleConfig::Constant
+    }
+
+    fn box_clone(&self) -> Box<dyn LearningRateSchedule> {
+        Box::new(self.clone())
+    }
+}
+
+// multiplies the learning rate by `gamma` every `every` epochs/steps
+#[derive(Debug, Clone)]
+pub struct StepDecay {
+    pub every: usize,
+    pub gamma: f64,
+    pub unit: ScheduleUnit,
+}
+
+impl LearningRateSchedule for StepDecay {
+    fn learning_rate(&mut self, base: f64, epoch: usize, step: usize) -> f64 {
+        base * self.gamma.powi((self.unit.pick(epoch, step) / self.every.max(1)) as i32)
+    }
+
+    fn config(&self) -> ScheduleConfig {
+        ScheduleConfig::StepDecay { every: self.every, gamma: self.gamma, unit: self.unit }
+    }
+
+    fn box_clone(&self) -> Box<dyn LearningRateSchedule> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExponentialDecay {
+    pub gamma: f64,
+    pub unit: ScheduleUnit,
+}
+
+impl LearningRateSchedule for ExponentialDecay {
+    fn learning_rate(&mut self, base: f64, epoch: usize, step: usize) -> f64 {
+        base * self.gamma.powi(self.unit.pick(epoch, step) as i32)
+    }
+
+    fn config(&self) -> ScheduleConfig {
+        ScheduleConfig::ExponentialDecay { gamma: self.gamma, unit: self.unit }
+    }
+
+    fn box_clone(&self) -> Box<dyn LearningRateSchedule> {
+        Box::new(self.clone())
+    }
+}
+
+// cosine annealing with warm restarts, every restart makes the period `period_multiplier` times longer
+#[derive(Debug, Clone)]
+pub struct CosineAnnealing {
+    pub period: usize,
+    pub period_multiplier: usize,
+    pub min_learning_rate: f64,
+    pub unit: ScheduleUnit,
+}
+
+impl LearningRateSchedule for CosineAnnealing {
+    fn learning_rate(&mut self, base: f64, epoch: usize, step: usize) -> f64 {
+        let mut t = self.unit.pick(epoch, step);
+        let mut period = self.period.max(1);
+        if self.period_multiplier <= 1 {
+            t %= period;
+        }
+        // the periods grow geometrically, so this takes a logarithmic number of restarts
+        while t >= period {
+            t -= period;
+            period *= self.period_multiplier;
+        }
+        self.min_learning_rate + (base - self.min_learning_rate) * 0.5 * (1.0 + (PI * t as f64 / period as f64).cos())
+    }
+
+    fn config(&self) -> ScheduleConfig {
+        ScheduleConfig::CosineAnnealing {
+            period: self.period,
+            period_multiplier: self.period_multiplier,
+            min_learning_rate: self.min_learning_rate,
+            unit: self.unit,
+        }
+    }
+
+    fn box_clone(&self) -> Box<dyn LearningRateSchedule> {
+        Box::new(self.clone())
+    }
+}
+
+// ramps the learning rate up linearly, afterwards `then` takes over (with the same counters)
+#[derive(Debug, Clone)]
+pub struct LinearWarmup {
+    pub warmup: usize,
+    pub unit: ScheduleUnit,
+    pub then: Box<dyn LearningRateSchedule>,
+}
+
+impl LearningRateSchedule for LinearWarmup {
+    fn learning_rate(&mut self, base: f64, epoch: usize, step: usize) -> f64 {
+        let t = self.unit.pick(epoch, step);
+        if t < self.warmup {
+            base * (t + 1) as f64 / self.warmup as f64
+        } else {
+            self.then.learning_rate(base, epoch, step)
+        }
+    }
+
+    fn observe(&mut self, metric: f64) {
+        self.then.observe(metric);
+    }
+
+    fn state(&self) -> Option<ScheduleState> {
+        self.then.state()
+    }
+
+    fn set_state(&mut self, state: ScheduleState) {
+        self.then.set_state(state);
+    }
+
+    fn config(&self) -> ScheduleConfig {
+        ScheduleConfig::LinearWarmup { warmup: self.warmup, unit: self.unit, then: Box::new(self.then.config()) }
+    }
+
+    fn box_clone(&self) -> Box<dyn LearningRateSchedule> {
+        Box::new(self.clone())
+    }
+}
+
+// multiplies the learning rate by `factor` once the observed metric didn't improve by more than
+// `threshold` (relative) for `patience` epochs
+#[derive(Debug, Clone)]
+pub struct ReduceOnPlateau {
+    pub factor: f64,
+    pub patience: usize,
+    pub threshold: f64,
+    pub min_learning_rate: f64,
+    best: f64,
+    bad_epochs: usize,
+    scale: f64,
+}
+
+impl ReduceOnPlateau {
+    pub fn new(factor: f64, patience: usize, threshold: f64, min_learning_rate: f64) -> ReduceOnPlateau {
+        ReduceOnPlateau { factor, patience, threshold, min_learning_rate, best: f64::INFINITY, bad_epochs: 0, scale: 1.0 }
+    }
+}
+
+impl LearningRateSchedule for ReduceOnPlateau {
+    fn learning_rate(&mut self, base: f64, _epoch: usize, _step: usize) -> f64 {
+        (base * self.scale).max(self.min_learning_rate.min(base))
+    }
+
+    fn observe(&mut self, metric: f64) {
+        if metric < self.best * (1.0 - self.threshold) {
+            self.best = metric;
+            self.bad_epochs = 0;
+        } else {
+            self.bad_epochs += 1;
+            if self.bad_epochs > self.patience {
+                self.scale *= self.factor;
+                self.bad_epochs = 0;
+            }
+        }
+    }
+
+    fn config(&self) -> ScheduleConfig {
+        ScheduleConfig::ReduceOnPlateau {
+            factor: self.factor,
+            patience: self.patience,
+            threshold: self.threshold,
+            min_learning_rate: self.min_learning_rate,
+        }
+    }
+
+    fn state(&self) -> Option<ScheduleState> {
+        Some(ScheduleState { best: Some(self.best).filter(|best| best.is_finite()), bad_epochs: self.bad_epochs, scale: self.scale })
+    }
+
+    fn set_state(&mut self, state: ScheduleState) {
+        self.best = state.best.unwrap_or(f64::INFINITY);
+        self.bad_epochs = state.bad_epochs;
+        self.scale = state.scale;
+    }
+
+    fn box_clone(&self) -> Box<dyn LearningRateSchedule> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rates(config: ScheduleConfig, base: f64, expected: &[(usize, f64)]) {
+        let mut schedule = config.build();
+        assert_eq!(schedule.config(), config);
+        for (t, rate) in expected {
+            // the unit decides which of the counters is used, the other one must not matter
+            let actual = schedule.learning_rate(base, *t, *t * 7 + 3);
+            assert!((actual - rate).abs() < 1e-12, "{:?} at {}: got {}, expected {}", config, t, actual, rate);
+        }
+    }
+
+    #[test]
+    fn step_decay() {
+        assert_rates(ScheduleConfig::StepDecay { every: 2, gamma: 0.5, unit: ScheduleUnit::Epoch }, 1.0,
+                     &[(0, 1.0), (1, 1.0), (2, 0.5), (3, 0.5), (4, 0.25)]);
+        let mut schedule = ScheduleConfig::StepDecay { every: 10, gamma: 0.1, unit: ScheduleUnit::Step }.build();
+        assert_eq!(schedule.learning_rate(1.0, 0, 9), 1.0);
+        assert!((schedule.learning_rate(1.0, 0, 25) - 0.01).abs() < 1e-12);
+    }
+
+    #[test]
+    fn exponential_decay() {
+        assert_rates(ScheduleConfig::ExponentialDecay { gamma: 0.9, unit: ScheduleUnit::Epoch }, 2.0,
+                     &[(0, 2.0), (1, 1.8), (3, 2.0 * 0.729)]);
+    }
+
+    #[test]
+    fn cosine_annealing_restarts() {
+        let config = |period_multiplier| ScheduleConfig::CosineAnnealing { period: 4, period_multiplier, min_learning_rate: 0.1, unit: ScheduleUnit::Epoch };
+        assert_rates(config(1), 1.1, &[(0, 1.1), (2, 0.6), (4, 1.1), (6, 0.6), (4002, 0.6), (usize::MAX / 64 * 4 + 2, 0.6)]);
+        assert_rates(config(0), 1.1, &[(1, 0.6 + 0.5 * std::f64::consts::FRAC_1_SQRT_2), (5, 0.6 + 0.5 * std::f64::consts::FRAC_1_SQRT_2)]);
+        // periods 4, 8, 16, ...
+        assert_rates(config(2), 1.1, &[(0, 1.1), (2, 0.6), (4, 1.1), (8, 0.6), (12, 1.1)]);
+    }
+
+    #[test]
+    fn linear_warmup_hands_over_with_the_same_counters() {
+        assert_rates(ScheduleConfig::LinearWarmup { warmup: 4, unit: ScheduleUnit::Epoch, then: Box::new(ScheduleConfig::Constant) }, 1.0,
+                     &[(0, 0.25), (1, 0.5), (3, 1.0), (10, 1.0)]);
+        let then = ScheduleConfig::StepDecay { every: 2, gamma: 0.5, unit: ScheduleUnit::Epoch };
+        assert_rates(ScheduleConfig::LinearWarmup { warmup: 2, unit: ScheduleUnit::Epoch, then: Box::new(then) }, 1.0,
+                     &[(0, 0.5), (1, 1.0), (2, 0.5), (4, 0.25)]);
+    }
+
+    #[test]
+    fn reduce_on_plateau() {
+        let mut schedule = ScheduleConfig::ReduceOnPlateau { factor: 0.5, patience: 1, threshold: 0.1, min_learning_rate: 0.1 }.build();
+        let mut rates = Vec::new();
+        // 0.95 isn't 10% better than 1.0, so it counts as a bad epoch
+        for metric in [1.0, 0.95, 1.0, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5] {
+            schedule.observe(metric);
+            rates.push(schedule.learning_rate(1.0, 0, 0));
+        }
+        assert_eq!(rates, vec![1.0, 1.0, 0.5, 0.5, 0.5, 0.25, 0.25, 0.125, 0.125, 0.1]);
+    }
+}